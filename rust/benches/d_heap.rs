@@ -3,14 +3,16 @@ use criterion::{criterion_group, criterion_main, Criterion};
 use advanced_datastructures::heaps::d_heap::DHeap;
 use advanced_datastructures::heaps::d_heap_arc::DHeap as ArcDHeap;
 use advanced_datastructures::heaps::d_way_heap_clone::DHeap as BasicDHeap;
+use advanced_datastructures::heaps::fibonacci::FibonacciHeap;
+use advanced_datastructures::heaps::pairing::PairingHeap;
 use rand::seq::SliceRandom;
 use rand::thread_rng;
 
 fn process_elements(values: Vec<i32>, copy: Vec<i32>) {
     let mut heap: DHeap<i32, 3> = DHeap::new(values);
     let element_mid = copy.len() / 2;
-    for element_index in element_mid..copy.len() {
-        let exists = heap.contains(&copy[element_index]);
+    for element in &copy[element_mid..] {
+        let exists = heap.contains(element);
         assert!(exists);
     }
     for n in &copy {
@@ -22,8 +24,8 @@ fn process_elements(values: Vec<i32>, copy: Vec<i32>) {
 fn process_elements_arc(values: Vec<i32>, copy: Vec<i32>) {
     let mut heap: ArcDHeap<i32, 3> = ArcDHeap::new(values);
     let element_mid = copy.len() / 2;
-    for element_index in element_mid..copy.len() {
-        let exists = heap.contains(&copy[element_index]);
+    for element in &copy[element_mid..] {
+        let exists = heap.contains(element);
         assert!(exists);
     }
     for n in &copy {
@@ -35,8 +37,8 @@ fn process_elements_arc(values: Vec<i32>, copy: Vec<i32>) {
 fn process_elements_basic(values: Vec<i32>, copy: Vec<i32>) {
     let mut heap: BasicDHeap<i32> = BasicDHeap::new(values, Some(3));
     let element_mid = copy.len() / 2;
-    for element_index in element_mid..copy.len() {
-        let exists = heap.contains(&copy[element_index]);
+    for element in &copy[element_mid..] {
+        let exists = heap.contains(element);
         assert!(exists);
     }
     for n in &copy {
@@ -45,6 +47,78 @@ fn process_elements_basic(values: Vec<i32>, copy: Vec<i32>) {
     }
 }
 
+fn process_elements_pairing(values: Vec<i32>, copy: Vec<i32>) {
+    let mut heap = PairingHeap::new();
+    for value in values {
+        heap.insert(value);
+    }
+    for n in &copy {
+        let num = heap.top().unwrap();
+        assert_eq!(*n, num)
+    }
+}
+
+fn process_elements_fibonacci(values: Vec<i32>, copy: Vec<i32>) {
+    let mut heap = FibonacciHeap::new();
+    for value in values {
+        heap.insert(value);
+    }
+    for n in &copy {
+        let num = heap.top().unwrap();
+        assert_eq!(*n, num)
+    }
+}
+
+/// Raises every other element above all of the others, then drains the heap,
+/// the access pattern of Dijkstra or Prim relaxing edges.
+fn decrease_keys_pairing(values: Vec<i32>, raise: i32) {
+    let mut heap = PairingHeap::new();
+    let handles: Vec<_> = values.iter().map(|&value| heap.insert(value)).collect();
+    for (handle, value) in handles.iter().zip(&values).step_by(2) {
+        assert!(heap.decrease_key(handle, value + raise));
+    }
+    while heap.top().is_some() {}
+}
+
+fn decrease_keys_fibonacci(values: Vec<i32>, raise: i32) {
+    let mut heap = FibonacciHeap::new();
+    let handles: Vec<_> = values.iter().map(|&value| heap.insert(value)).collect();
+    for (handle, value) in handles.iter().zip(&values).step_by(2) {
+        assert!(heap.decrease_key(handle, value + raise));
+    }
+    while heap.top().is_some() {}
+}
+
+/// The same with `update`, which finds the element through the position map.
+fn decrease_keys_arc(values: Vec<i32>, raise: i32) {
+    let mut heap: ArcDHeap<i32, 3> = ArcDHeap::new(values.clone());
+    for &value in values.iter().step_by(2) {
+        heap.update(value, value + raise);
+    }
+    while heap.top().is_some() {}
+}
+
+/// Prints the sift counts for one run of each `DHeap` variant, to read next to
+/// the timings. Run with `cargo bench --bench d_heap --features instrumented`.
+#[cfg(feature = "instrumented")]
+fn print_stats(values: &[i32]) {
+    let mut heap: DHeap<i32, 3> = DHeap::new(values.to_vec());
+    while heap.top().is_some() {}
+    println!("unsafe: {}", heap.stats());
+
+    let mut heap: ArcDHeap<i32, 3> = ArcDHeap::new(values.to_vec());
+    for _ in values {
+        heap.top();
+    }
+    println!("arc: {}", heap.stats());
+
+    let mut heap: BasicDHeap<i32> = BasicDHeap::new(values.to_vec(), Some(3));
+    for _ in values {
+        heap.top();
+    }
+    println!("basic: {}", heap.stats());
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let mut group = c.benchmark_group("d_heap_one_ten_thousand");
     let count = 10_000;
@@ -54,6 +128,8 @@ fn criterion_benchmark(c: &mut Criterion) {
     copy.sort();
     copy.reverse();
     group.significance_level(0.1).sample_size(20);
+    #[cfg(feature = "instrumented")]
+    print_stats(&values);
     group.bench_function("arc", |b| {
         b.iter(|| process_elements_arc(values.clone(), copy.clone()))
     });
@@ -63,6 +139,25 @@ fn criterion_benchmark(c: &mut Criterion) {
     group.bench_function("unsafe", |b| {
         b.iter(|| process_elements(values.clone(), copy.clone()))
     });
+    group.bench_function("pairing", |b| {
+        b.iter(|| process_elements_pairing(values.clone(), copy.clone()))
+    });
+    group.bench_function("fibonacci", |b| {
+        b.iter(|| process_elements_fibonacci(values.clone(), copy.clone()))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("decrease_key_one_ten_thousand");
+    group.significance_level(0.1).sample_size(20);
+    group.bench_function("arc", |b| {
+        b.iter(|| decrease_keys_arc(values.clone(), count))
+    });
+    group.bench_function("pairing", |b| {
+        b.iter(|| decrease_keys_pairing(values.clone(), count))
+    });
+    group.bench_function("fibonacci", |b| {
+        b.iter(|| decrease_keys_fibonacci(values.clone(), count))
+    });
     group.finish()
 }
 
@@ -0,0 +1,44 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use advanced_datastructures::heaps::merge::{kmerge, kmerge_loser_tree};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const TOTAL: usize = 200_000;
+
+fn create_runs(k: usize) -> Vec<Vec<u64>> {
+    let mut rng = StdRng::seed_from_u64(40);
+    (0..k)
+        .map(|_| {
+            let mut run: Vec<u64> = (0..TOTAL / k).map(|_| rng.gen()).collect();
+            run.sort_unstable();
+            run
+        })
+        .collect()
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("kmerge_two_hundred_thousand");
+    group.significance_level(0.1).sample_size(20);
+    for k in [2, 10, 100, 1_000, 10_000] {
+        let runs = create_runs(k);
+        let iters = || runs.iter().map(|run| run.iter());
+        group.bench_function(BenchmarkId::new("d_heap_2", k), |b| {
+            b.iter(|| kmerge::<_, 2>(iters()).count())
+        });
+        group.bench_function(BenchmarkId::new("d_heap_4", k), |b| {
+            b.iter(|| kmerge::<_, 4>(iters()).count())
+        });
+        group.bench_function(BenchmarkId::new("d_heap_8", k), |b| {
+            b.iter(|| kmerge::<_, 8>(iters()).count())
+        });
+        group.bench_function(BenchmarkId::new("loser_tree", k), |b| {
+            b.iter(|| kmerge_loser_tree(iters()).count())
+        });
+    }
+    group.finish()
+}
+
+// cargo bench --bench kmerge -- --plotting-backend plotters
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
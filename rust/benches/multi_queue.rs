@@ -0,0 +1,80 @@
+use std::sync::Mutex;
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+
+use advanced_datastructures::heaps::d_heap::DHeap;
+use advanced_datastructures::heaps::multi_queue::{measure_rank_error, MultiQueue};
+
+const THREADS: u64 = 4;
+const OPERATIONS: u64 = 50_000;
+const PREFILLED: u64 = 100_000;
+
+fn process_elements_multi_queue(queue: &MultiQueue<u64>) {
+    std::thread::scope(|scope| {
+        for thread_index in 0..THREADS {
+            scope.spawn(move || {
+                for n in 0..OPERATIONS {
+                    queue.insert(thread_index * OPERATIONS + n);
+                    if n % 2 == 1 {
+                        queue.pop();
+                        queue.pop();
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn process_elements_mutex(queue: &Mutex<DHeap<u64, 4>>) {
+    std::thread::scope(|scope| {
+        for thread_index in 0..THREADS {
+            scope.spawn(move || {
+                for n in 0..OPERATIONS {
+                    queue.lock().unwrap().insert(thread_index * OPERATIONS + n);
+                    if n % 2 == 1 {
+                        queue.lock().unwrap().top();
+                        queue.lock().unwrap().top();
+                    }
+                }
+            });
+        }
+    });
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("multi_queue_four_threads");
+    group.significance_level(0.1).sample_size(20);
+    group.bench_function("mutex_d_heap", |b| {
+        b.iter_batched(
+            || Mutex::new(DHeap::new((0..PREFILLED).collect())),
+            |queue| process_elements_mutex(&queue),
+            BatchSize::LargeInput,
+        )
+    });
+    for queues_per_thread in [1, 2, 4] {
+        let stats = measure_rank_error(
+            &MultiQueue::<u64>::new(THREADS as usize, queues_per_thread),
+            (0..100_000).collect(),
+        );
+        println!("c = {queues_per_thread}: rank error {stats:?}");
+        group.bench_function(BenchmarkId::new("multi_queue", queues_per_thread), |b| {
+            b.iter_batched(
+                || {
+                    let queue = MultiQueue::new(THREADS as usize, queues_per_thread);
+                    for n in 0..PREFILLED {
+                        queue.insert(n);
+                    }
+                    queue
+                },
+                |queue| process_elements_multi_queue(&queue),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish()
+}
+
+// cargo bench --bench multi_queue -- --plotting-backend plotters
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,75 @@
+use std::cmp::Reverse;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use advanced_datastructures::heaps::d_heap::DHeap;
+use advanced_datastructures::heaps::radix::RadixHeap;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+type Graph = Vec<Vec<(usize, u64)>>;
+
+fn create_graph(nodes: usize, edges_per_node: usize) -> Graph {
+    let mut rng = StdRng::seed_from_u64(32);
+    (0..nodes)
+        .map(|_| {
+            (0..edges_per_node)
+                .map(|_| (rng.gen_range(0..nodes), rng.gen_range(1..1_000)))
+                .collect()
+        })
+        .collect()
+}
+
+fn shortest_paths_radix(graph: &Graph) -> Vec<u64> {
+    let mut distances = vec![u64::MAX; graph.len()];
+    let mut heap = RadixHeap::new();
+    distances[0] = 0;
+    heap.push(0, 0);
+    while let Some((distance, node)) = heap.pop() {
+        if distance > distances[node] {
+            continue;
+        }
+        for &(next, weight) in &graph[node] {
+            let next_distance = distance + weight;
+            if next_distance < distances[next] {
+                distances[next] = next_distance;
+                heap.push(next_distance, next);
+            }
+        }
+    }
+    distances
+}
+
+fn shortest_paths_d_heap(graph: &Graph) -> Vec<u64> {
+    let mut distances = vec![u64::MAX; graph.len()];
+    let mut heap: DHeap<Reverse<(u64, usize)>, 4> = DHeap::new(vec![]);
+    distances[0] = 0;
+    heap.insert(Reverse((0, 0)));
+    while let Some(Reverse((distance, node))) = heap.top() {
+        if distance > distances[node] {
+            continue;
+        }
+        for &(next, weight) in &graph[node] {
+            let next_distance = distance + weight;
+            if next_distance < distances[next] {
+                distances[next] = next_distance;
+                heap.insert(Reverse((next_distance, next)));
+            }
+        }
+    }
+    distances
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("shortest_paths_one_hundred_thousand");
+    let graph = create_graph(100_000, 8);
+    assert_eq!(shortest_paths_radix(&graph), shortest_paths_d_heap(&graph));
+    group.significance_level(0.1).sample_size(20);
+    group.bench_function("radix", |b| b.iter(|| shortest_paths_radix(&graph)));
+    group.bench_function("d_heap", |b| b.iter(|| shortest_paths_d_heap(&graph)));
+    group.finish()
+}
+
+// cargo bench --bench radix_heap -- --plotting-backend plotters
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,34 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+use advanced_datastructures::heaps::sort::{heapsort, partial_sort};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sort_one_hundred_thousand");
+    let count = 100_000;
+    let mut values: Vec<i32> = (0..count).collect();
+    values.shuffle(&mut thread_rng());
+    group.significance_level(0.1).sample_size(20);
+    group.bench_function("sort_unstable", |b| {
+        b.iter(|| values.clone().sort_unstable())
+    });
+    group.bench_function(BenchmarkId::new("heapsort", 2), |b| {
+        b.iter(|| heapsort::<_, 2>(&mut values.clone()))
+    });
+    group.bench_function(BenchmarkId::new("heapsort", 3), |b| {
+        b.iter(|| heapsort::<_, 3>(&mut values.clone()))
+    });
+    group.bench_function(BenchmarkId::new("heapsort", 4), |b| {
+        b.iter(|| heapsort::<_, 4>(&mut values.clone()))
+    });
+    group.bench_function(BenchmarkId::new("partial_sort_100", 4), |b| {
+        b.iter(|| partial_sort::<_, 4>(&mut values.clone(), 100))
+    });
+    group.finish()
+}
+
+// cargo bench --bench sort -- --plotting-backend plotters
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
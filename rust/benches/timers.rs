@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use advanced_datastructures::heaps::delay::DelayQueue;
+use advanced_datastructures::timers::TimingWheel;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const TIMERS: usize = 100_000;
+const HORIZON_MILLIS: u64 = 30_000;
+const STEP_MILLIS: u64 = 10;
+
+fn create_delays() -> Vec<u64> {
+    let mut rng = StdRng::seed_from_u64(38);
+    (0..TIMERS)
+        .map(|_| rng.gen_range(1..HORIZON_MILLIS))
+        .collect()
+}
+
+// Schedule every timer, cancel every other one (a connection that saw
+// traffic) and then run the clock until the rest have fired.
+fn process_timers_wheel(delays: &[u64], start: Instant) -> usize {
+    let mut wheel = TimingWheel::new(start, Duration::from_millis(1), 64, 4);
+    let keys: Vec<_> = delays
+        .iter()
+        .map(|&millis| wheel.insert_after(millis, Duration::from_millis(millis)))
+        .collect();
+    for key in keys.into_iter().step_by(2) {
+        wheel.cancel(key);
+    }
+    let mut fired = 0;
+    for now in (0..=HORIZON_MILLIS).step_by(STEP_MILLIS as usize) {
+        fired += wheel.advance(start + Duration::from_millis(now)).len();
+    }
+    fired
+}
+
+fn process_timers_delay_queue(delays: &[u64], start: Instant) -> usize {
+    let mut queue = DelayQueue::new();
+    let keys: Vec<_> = delays
+        .iter()
+        .map(|&millis| queue.insert_at(millis, start + Duration::from_millis(millis)))
+        .collect();
+    for key in keys.into_iter().step_by(2) {
+        queue.cancel(key);
+    }
+    let mut fired = 0;
+    for now in (0..=HORIZON_MILLIS).step_by(STEP_MILLIS as usize) {
+        let now = start + Duration::from_millis(now);
+        fired += std::iter::from_fn(|| queue.poll_expired(now)).count();
+    }
+    fired
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("timers_one_hundred_thousand");
+    let delays = create_delays();
+    let start = Instant::now();
+    assert_eq!(
+        process_timers_wheel(&delays, start),
+        process_timers_delay_queue(&delays, start)
+    );
+    group.significance_level(0.1).sample_size(20);
+    group.bench_function("timing_wheel", |b| {
+        b.iter(|| process_timers_wheel(&delays, start))
+    });
+    group.bench_function("delay_queue", |b| {
+        b.iter(|| process_timers_delay_queue(&delays, start))
+    });
+    group.finish()
+}
+
+// cargo bench --bench timers -- --plotting-backend plotters
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,7 @@
+#![no_main]
+
+use advanced_datastructures::heaps::d_way_heap_clone::DHeap;
+use advanced_datastructures_fuzz::{run, Operation};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|operations: Vec<Operation>| run::<DHeap<u8>>(&operations));
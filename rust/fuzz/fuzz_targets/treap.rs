@@ -0,0 +1,6 @@
+#![no_main]
+
+use advanced_datastructures_fuzz::{run_treap, TreapOperation};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|operations: Vec<TreapOperation>| run_treap(&operations));
@@ -0,0 +1,266 @@
+//! Drives the heaps and the treap with arbitrary operation sequences and
+//! checks every result against a `BTreeMap` model.
+
+use std::collections::BTreeMap;
+
+use advanced_datastructures::advanced::treap::Treap;
+use advanced_datastructures::heaps::{d_heap, d_heap_arc, d_way_heap_clone};
+use arbitrary::Arbitrary;
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum Operation {
+    Insert(u8),
+    Top,
+    Remove(u8),
+    Update(u8, u8),
+    Contains(u8),
+}
+
+/// The operations shared by the heaps under test. The heaps keyed by a
+/// position map refuse equal elements, so `insert` and `update` report
+/// whether they changed the heap.
+pub trait Heap {
+    fn create() -> Self;
+    fn insert(&mut self, element: u8) -> bool;
+    fn top(&mut self) -> Option<u8>;
+    fn peek(&self) -> Option<u8>;
+    fn remove(&mut self, element: u8);
+    fn update(&mut self, old_element: u8, new_element: u8) -> bool;
+    fn contains(&self, element: u8) -> bool;
+    fn size(&self) -> usize;
+}
+
+impl Heap for d_heap::DHeap<u8, 3> {
+    fn create() -> Self {
+        d_heap::DHeap::new(vec![])
+    }
+
+    fn insert(&mut self, element: u8) -> bool {
+        self.insert(element);
+        true
+    }
+
+    fn top(&mut self) -> Option<u8> {
+        self.top()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.peek().copied()
+    }
+
+    fn remove(&mut self, element: u8) {
+        self.remove(element)
+    }
+
+    fn update(&mut self, old_element: u8, new_element: u8) -> bool {
+        let present = self.contains(&old_element);
+        self.update(old_element, new_element);
+        present
+    }
+
+    fn contains(&self, element: u8) -> bool {
+        self.contains(&element)
+    }
+
+    fn size(&self) -> usize {
+        self.size()
+    }
+}
+
+impl Heap for d_heap_arc::DHeap<u8, 3> {
+    fn create() -> Self {
+        d_heap_arc::DHeap::new(vec![])
+    }
+
+    fn insert(&mut self, element: u8) -> bool {
+        self.insert(element)
+    }
+
+    fn top(&mut self) -> Option<u8> {
+        self.top()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.peek().copied()
+    }
+
+    fn remove(&mut self, element: u8) {
+        self.remove(element)
+    }
+
+    fn update(&mut self, old_element: u8, new_element: u8) -> bool {
+        self.update(old_element, new_element)
+    }
+
+    fn contains(&self, element: u8) -> bool {
+        self.contains(&element)
+    }
+
+    fn size(&self) -> usize {
+        self.size()
+    }
+}
+
+impl Heap for d_way_heap_clone::DHeap<u8> {
+    fn create() -> Self {
+        d_way_heap_clone::DHeap::new(vec![], Some(3))
+    }
+
+    fn insert(&mut self, element: u8) -> bool {
+        self.insert(element)
+    }
+
+    fn top(&mut self) -> Option<u8> {
+        self.top()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.peek().copied()
+    }
+
+    fn remove(&mut self, element: u8) {
+        self.remove(element)
+    }
+
+    fn update(&mut self, old_element: u8, new_element: u8) -> bool {
+        self.update(old_element, new_element)
+    }
+
+    fn contains(&self, element: u8) -> bool {
+        self.contains(&element)
+    }
+
+    fn size(&self) -> usize {
+        self.size()
+    }
+}
+
+/// A multiset of elements, the reference the heaps are checked against.
+#[derive(Default)]
+struct Model {
+    counts: BTreeMap<u8, usize>,
+    size: usize,
+}
+
+impl Model {
+    fn insert(&mut self, element: u8) {
+        *self.counts.entry(element).or_default() += 1;
+        self.size += 1;
+    }
+
+    fn remove(&mut self, element: u8) -> bool {
+        match self.counts.get_mut(&element) {
+            Some(1) => {
+                self.counts.remove(&element);
+            }
+            Some(count) => *count -= 1,
+            None => return false,
+        }
+        self.size -= 1;
+        true
+    }
+
+    fn max(&self) -> Option<u8> {
+        self.counts.keys().next_back().copied()
+    }
+
+    fn contains(&self, element: u8) -> bool {
+        self.counts.contains_key(&element)
+    }
+}
+
+/// Applies `operations` to a fresh `H` and to the model, panicking on the
+/// first difference between them.
+pub fn run<H: Heap>(operations: &[Operation]) {
+    let mut heap = H::create();
+    let mut model = Model::default();
+    for &operation in operations {
+        match operation {
+            Operation::Insert(element) => {
+                if heap.insert(element) {
+                    model.insert(element);
+                } else {
+                    assert!(model.contains(element), "{operation:?}");
+                }
+            }
+            Operation::Top => {
+                let expected = model.max();
+                if let Some(max) = expected {
+                    model.remove(max);
+                }
+                assert_eq!(expected, heap.top(), "{operation:?}");
+            }
+            Operation::Remove(element) => {
+                heap.remove(element);
+                model.remove(element);
+            }
+            Operation::Update(old_element, new_element) => {
+                if heap.update(old_element, new_element) {
+                    assert!(model.remove(old_element), "{operation:?}");
+                    model.insert(new_element);
+                } else {
+                    let clashes = old_element != new_element && model.contains(new_element);
+                    assert!(!model.contains(old_element) || clashes, "{operation:?}");
+                }
+            }
+            Operation::Contains(element) => {
+                assert_eq!(
+                    model.contains(element),
+                    heap.contains(element),
+                    "{operation:?}"
+                );
+            }
+        }
+        assert_eq!(model.size, heap.size(), "{operation:?}");
+        assert_eq!(model.max(), heap.peek(), "{operation:?}");
+    }
+}
+
+#[derive(Arbitrary, Debug, Clone, Copy)]
+pub enum TreapOperation {
+    Insert(u8, u8),
+    Top,
+    Remove(u8),
+    Get(u8),
+}
+
+/// Applies `operations` to a fresh [`Treap`] and to a `BTreeMap` from keys to
+/// priorities, panicking on the first difference between them.
+pub fn run_treap(operations: &[TreapOperation]) {
+    let mut treap = Treap::new();
+    let mut model: BTreeMap<u8, u8> = BTreeMap::new();
+    for &operation in operations {
+        match operation {
+            TreapOperation::Insert(key, priority) => {
+                assert_eq!(
+                    model.insert(key, priority),
+                    treap.insert(key, priority),
+                    "{operation:?}"
+                );
+            }
+            TreapOperation::Top => {
+                // equal priorities may come out in any order, so check the
+                // entry rather than which key it is
+                let max = model.values().max().copied();
+                let top = treap.top();
+                assert_eq!(max, top.map(|(_, priority)| priority), "{operation:?}");
+                if let Some((key, priority)) = top {
+                    assert_eq!(Some(priority), model.remove(&key), "{operation:?}");
+                }
+            }
+            TreapOperation::Remove(key) => {
+                assert_eq!(model.remove(&key), treap.remove(&key), "{operation:?}");
+            }
+            TreapOperation::Get(key) => {
+                assert_eq!(model.get(&key), treap.get(&key), "{operation:?}");
+            }
+        }
+        assert_eq!(model.len(), treap.size(), "{operation:?}");
+        assert_eq!(
+            model.values().max(),
+            treap.peek().map(|(_, priority)| priority),
+            "{operation:?}"
+        );
+        assert!(treap.iter().eq(model.iter()), "{operation:?}");
+    }
+}
@@ -1,69 +1,384 @@
-use std::{
-    cell::RefCell,
-    rc::{Rc, Weak},
+use alloc::{boxed::Box, string::String, vec::Vec};
+use core::{
+    cmp::Ordering,
+    fmt::{Debug, Write},
 };
 
-// Sanity check this...
-#[derive(PartialEq)]
-struct TreapEntry<K, P: std::cmp::PartialOrd> {
-    pub key: K,
-    pub priority: P,
-}
+use crate::heaps::render::escape;
 
-impl<K, P> std::cmp::PartialOrd for TreapEntry<K, P>
-where
-    P: std::cmp::PartialOrd,
-    K: PartialEq,
-{
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.priority.partial_cmp(&other.priority)
-    }
-}
-// need to implement cmp
+type NodeLink<K, P> = Option<Box<TreapNode<K, P>>>;
 
 #[derive(Debug)]
 struct TreapNode<K, P> {
     key: K,
     priority: P,
-    left: Option<NodeLink<K, P>>,
-    right: Option<NodeLink<K, P>>,
-    parent: Option<Weak<NodeLink<K, P>>>,
+    left: NodeLink<K, P>,
+    right: NodeLink<K, P>,
 }
 
-type NodeLink<K, P> = Rc<RefCell<TreapNode<K, P>>>;
+/// A map from keys to priorities that is a binary search tree over the keys
+/// and a max-heap over the priorities, so the entry with the highest priority
+/// is always at the root.
+///
+/// Operations take time proportional to the depth of the tree, which is
+/// O(log n) expected when the priorities are independent of the keys, for
+/// example random. Priorities that follow the keys degrade it into a path and
+/// every operation to O(n); that is slow but never overflows the stack, as
+/// nothing recurses over the depth.
+#[derive(Debug)]
+pub struct Treap<K: Ord, P: Ord> {
+    root: NodeLink<K, P>,
+    size: usize,
+}
 
-impl<K, P> TreapNode<K, P>
-where
-    K: std::cmp::PartialOrd,
-    P: Ord,
-{
-    pub fn new(key: K, priority: P) -> Self {
-        TreapNode {
+impl<K: Ord, P: Ord> Treap<K, P> {
+    pub fn new() -> Self {
+        Treap {
+            root: None,
+            size: 0,
+        }
+    }
+
+    /// Inserts `key` with `priority`, returning the key's previous priority if
+    /// it was already present.
+    pub fn insert(&mut self, key: K, priority: P) -> Option<P> {
+        let previous = self.remove(&key);
+        let (left, right) = split(self.root.take(), &key);
+        let node = Box::new(TreapNode {
             key,
             priority,
-            parent: None,
             left: None,
             right: None,
+        });
+        self.root = merge(merge(left, Some(node)), right);
+        self.size += 1;
+        previous
+    }
+
+    /// Removes `key`, returning its priority if it was present.
+    pub fn remove(&mut self, key: &K) -> Option<P> {
+        let mut link = &mut self.root;
+        while link.as_ref().is_some_and(|node| node.key != *key) {
+            let node = link.as_mut().expect("checked by the loop condition");
+            link = if *key < node.key {
+                &mut node.left
+            } else {
+                &mut node.right
+            };
+        }
+        let mut node = link.take()?;
+        *link = merge(node.left.take(), node.right.take());
+        self.size -= 1;
+        Some(node.priority)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&P> {
+        let mut link = &self.root;
+        while let Some(node) = link {
+            link = match key.cmp(&node.key) {
+                Ordering::Less => &node.left,
+                Ordering::Greater => &node.right,
+                Ordering::Equal => return Some(&node.priority),
+            };
+        }
+        None
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// The entry with the highest priority.
+    pub fn peek(&self) -> Option<(&K, &P)> {
+        self.root.as_ref().map(|root| (&root.key, &root.priority))
+    }
+
+    /// Removes and returns the entry with the highest priority.
+    pub fn top(&mut self) -> Option<(K, P)> {
+        let mut root = self.root.take()?;
+        self.root = merge(root.left.take(), root.right.take());
+        self.size -= 1;
+        Some((root.key, root.priority))
+    }
+
+    /// The entries in ascending key order.
+    pub fn iter(&self) -> Iter<'_, K, P> {
+        let mut iter = Iter { stack: Vec::new() };
+        iter.push_left_spine(&self.root);
+        iter
+    }
+
+    /// Renders the treap as a Graphviz digraph, one node per entry labelled
+    /// with its key and priority, and an edge from each node to its children.
+    pub fn to_dot(&self) -> String
+    where
+        K: Debug,
+        P: Debug,
+    {
+        let mut dot = String::new();
+        writeln!(dot, "digraph Treap {{").unwrap();
+        let mut next_id = 0;
+        let mut pending = Vec::new();
+        if let Some(root) = &self.root {
+            pending.push((&**root, next_id));
+            next_id += 1;
         }
+        // number the nodes in pre-order, left before right
+        while let Some((node, id)) = pending.pop() {
+            let label = escape(&alloc::format!("{:?}, {:?}", node.key, node.priority));
+            writeln!(dot, "    n{id} [label=\"{label}\"];").unwrap();
+            let mut children = Vec::new();
+            for child in [&node.left, &node.right].into_iter().flatten() {
+                writeln!(dot, "    n{id} -> n{next_id};").unwrap();
+                children.push((&**child, next_id));
+                next_id += 1;
+            }
+            pending.extend(children.into_iter().rev());
+        }
+        dot.push_str("}\n");
+        dot
     }
-    pub fn add_element(&mut self, value: K, priority: P) {
-        let potential_child = if &value < &self.key {
-            &mut self.left
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<K: Ord, P: Ord> Default for Treap<K, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, P: Ord> Drop for Treap<K, P> {
+    fn drop(&mut self) {
+        // unlink iteratively, a deep tree would otherwise overflow the stack
+        // through recursive Box drops
+        let mut pending: Vec<Box<TreapNode<K, P>>> = self.root.take().into_iter().collect();
+        while let Some(mut node) = pending.pop() {
+            pending.extend(node.left.take());
+            pending.extend(node.right.take());
+        }
+    }
+}
+
+/// Iterator over the entries of a [`Treap`] in ascending key order.
+pub struct Iter<'a, K, P> {
+    stack: Vec<&'a TreapNode<K, P>>,
+}
+
+impl<'a, K, P> Iter<'a, K, P> {
+    fn push_left_spine(&mut self, mut link: &'a NodeLink<K, P>) {
+        while let Some(node) = link {
+            self.stack.push(node);
+            link = &node.left;
+        }
+    }
+}
+
+impl<'a, K, P> Iterator for Iter<'a, K, P> {
+    type Item = (&'a K, &'a P);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.push_left_spine(&node.right);
+        Some((&node.key, &node.priority))
+    }
+}
+
+/// Splits `link` into the keys below `key` and the rest.
+///
+/// Walks down iteratively, appending each node to the tail of the side it
+/// belongs to, so a degenerate tree can't overflow the stack.
+fn split<K: Ord, P>(mut link: NodeLink<K, P>, key: &K) -> (NodeLink<K, P>, NodeLink<K, P>) {
+    let mut left = None;
+    let mut right = None;
+    let mut left_tail = &mut left;
+    let mut right_tail = &mut right;
+    while let Some(mut node) = link {
+        if node.key < *key {
+            link = node.right.take();
+            left_tail = &mut left_tail.insert(node).right;
         } else {
-            &mut self.right
+            link = node.left.take();
+            right_tail = &mut right_tail.insert(node).left;
+        }
+    }
+    (left, right)
+}
+
+/// Joins two treaps where every key in `left` is below every key in `right`.
+///
+/// Like [`split`], it builds the result top down through a tail link rather
+/// than recursing.
+fn merge<K, P: Ord>(mut left: NodeLink<K, P>, mut right: NodeLink<K, P>) -> NodeLink<K, P> {
+    let mut merged = None;
+    let mut tail = &mut merged;
+    loop {
+        match (left, right) {
+            (None, rest) | (rest, None) => {
+                *tail = rest;
+                return merged;
+            }
+            (Some(mut left_node), Some(mut right_node)) => {
+                if left_node.priority >= right_node.priority {
+                    left = left_node.right.take();
+                    right = Some(right_node);
+                    tail = &mut tail.insert(left_node).right;
+                } else {
+                    left = Some(left_node);
+                    right = right_node.left.take();
+                    tail = &mut tail.insert(right_node).left;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::collections::BTreeMap;
+
+    fn create_treap() -> Treap<char, u64> {
+        let mut treap = Treap::new();
+        for (key, priority) in ('a'..).zip([9, 10, 9, 8, 7, 5, 3, 8]) {
+            treap.insert(key, priority);
+        }
+        treap
+    }
+
+    /// Checks the search tree order over keys and the heap order over
+    /// priorities below `link`.
+    fn assert_valid<K: Ord, P: Ord>(link: &NodeLink<K, P>) {
+        let Some(node) = link else {
+            return;
         };
-        match potential_child {
-            Some(child) => child.add_element(value, priority),
-            None => {
-                let treap_node = TreapNode::new(value, priority);
-                *potential_child = Some(Box::new(treap_node));
+        for child in [&node.left, &node.right].into_iter().flatten() {
+            assert!(child.priority <= node.priority);
+        }
+        if let Some(left) = &node.left {
+            assert!(left.key < node.key);
+        }
+        if let Some(right) = &node.right {
+            assert!(right.key > node.key);
+        }
+        assert_valid(&node.left);
+        assert_valid(&node.right);
+    }
+
+    #[test]
+    fn it_should_pop_entries_by_priority() {
+        let mut treap = create_treap();
+        assert_valid(&treap.root);
+        assert_eq!(Some((&'b', &10)), treap.peek());
+        let mut priorities = vec![];
+        while let Some((_, priority)) = treap.top() {
+            priorities.push(priority);
+        }
+        assert_eq!(vec![10, 9, 9, 8, 8, 7, 5, 3], priorities);
+        assert!(treap.is_empty());
+    }
+
+    #[test]
+    fn it_should_iterate_in_key_order() {
+        let treap = create_treap();
+        let keys: Vec<char> = treap.iter().map(|(key, _)| *key).collect();
+        assert_eq!(('a'..='h').collect::<Vec<_>>(), keys);
+    }
+
+    #[test]
+    fn it_should_replace_and_remove_entries() {
+        let mut treap = create_treap();
+        assert_eq!(Some(3), treap.insert('g', 20));
+        assert_eq!(Some((&'g', &20)), treap.peek());
+        assert_eq!(Some(10), treap.remove(&'b'));
+        assert_eq!(None, treap.remove(&'b'));
+        assert_eq!(Some(&9), treap.get(&'a'));
+        assert!(!treap.contains_key(&'z'));
+        assert_eq!(7, treap.size());
+        assert_valid(&treap.root);
+    }
+
+    #[test]
+    fn it_should_match_a_map_for_random_operations() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut treap = Treap::new();
+        let mut model = BTreeMap::new();
+        for _ in 0..5_000 {
+            let key: u8 = rng.gen_range(0..64);
+            match rng.gen_range(0..3) {
+                0 => {
+                    let priority: u32 = rng.gen();
+                    assert_eq!(model.insert(key, priority), treap.insert(key, priority));
+                }
+                1 => assert_eq!(model.remove(&key), treap.remove(&key)),
+                _ => {
+                    let max = model.values().max().copied();
+                    let top = treap.top();
+                    assert_eq!(max, top.map(|(_, priority)| priority));
+                    if let Some((key, priority)) = top {
+                        assert_eq!(Some(priority), model.remove(&key));
+                    }
+                }
             }
+            assert_eq!(model.len(), treap.size());
         }
+        assert_valid(&treap.root);
+        assert!(treap.iter().eq(model.iter()));
     }
+
+    #[test]
+    fn it_should_export_keys_and_priorities_to_dot() {
+        let mut treap = Treap::new();
+        for (key, priority) in [('a', 1), ('b', 3), ('c', 2), ('d', 0)] {
+            treap.insert(key, priority);
+        }
+        let expected = "\
+digraph Treap {
+    n0 [label=\"'b', 3\"];
+    n0 -> n1;
+    n0 -> n2;
+    n1 [label=\"'a', 1\"];
+    n2 [label=\"'c', 2\"];
+    n2 -> n3;
+    n3 [label=\"'d', 0\"];
 }
+";
+        assert_eq!(expected, treap.to_dot());
+        assert_eq!("digraph Treap {\n}\n", Treap::<u8, u8>::new().to_dot());
+    }
 
-// struct Treap<T: std::cmp::PartialOrd, E: Ord> {
-//     root: Node<T, E>,
-// }
+    #[test]
+    fn it_should_split_and_merge_deep_treaps_without_overflowing() {
+        // rising priorities put every new key at the root, so this builds a
+        // single left leaning path; inserting and removing the smallest key
+        // then splits and merges along all of it
+        let mut treap = Treap::new();
+        for key in 1..200_000u64 {
+            treap.insert(key, key);
+        }
+        assert_eq!(None, treap.insert(0, 0));
+        assert_eq!(Some(&0), treap.get(&0));
+        assert_eq!(Some(0), treap.remove(&0));
+        assert_eq!(None, treap.insert(0, 200_000));
+        assert_eq!(Some((0, 200_000)), treap.top());
+        assert_eq!(Some((199_999, 199_999)), treap.top());
+        assert_eq!(199_998, treap.size());
+    }
 
-// Try using arena based...?
+    #[test]
+    fn it_should_drop_deep_treaps_without_overflowing() {
+        let mut treap = Treap::new();
+        for key in 0..200_000u64 {
+            treap.insert(key, key);
+        }
+        assert_eq!(Some((&199_999, &199_999)), treap.peek());
+        drop(treap);
+    }
+}
@@ -0,0 +1,4 @@
+pub mod format;
+pub mod huffman;
+#[cfg(test)]
+mod test_support;
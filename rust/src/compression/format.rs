@@ -0,0 +1,233 @@
+//! A self-describing container for Huffman compressed data.
+//!
+//! | bytes | field                                        |
+//! |-------|----------------------------------------------|
+//! | 4     | magic, `HUF\0`                               |
+//! | 1     | format version                               |
+//! | 256   | canonical code length of each byte value     |
+//! | 8     | original length, little endian               |
+//! | 4     | CRC-32 of the original data, little endian   |
+//! | rest  | the packed codes                             |
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::huffman::{self, HuffmanCode, HuffmanError};
+
+pub const MAGIC: [u8; 4] = *b"HUF\0";
+pub const VERSION: u8 = 1;
+
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 256 + 8 + 4;
+
+/// Why [`decompress`] could not recover the original data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormatError {
+    /// Shorter than a header, holding this many bytes.
+    TruncatedHeader(usize),
+    BadMagic,
+    UnsupportedVersion(u8),
+    InvalidCodeLengths,
+    /// The packed codes end before the original length is reached.
+    TruncatedData,
+    /// The packed codes hold a bit sequence that no byte is coded as.
+    CorruptData,
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatError::TruncatedHeader(length) => write!(
+                f,
+                "file is truncated: {length} bytes is shorter than the {HEADER_LENGTH} byte header"
+            ),
+            FormatError::BadMagic => write!(f, "not a compressed file: bad magic number"),
+            FormatError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {version}, expected {VERSION}"
+            ),
+            FormatError::InvalidCodeLengths => {
+                write!(f, "file is corrupt: code lengths do not form a prefix code")
+            }
+            FormatError::TruncatedData => {
+                write!(f, "file is truncated: compressed data ends early")
+            }
+            FormatError::CorruptData => write!(f, "file is corrupt: invalid compressed data"),
+            FormatError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "file is corrupt: checksum {actual:#010x} does not match {expected:#010x}"
+            ),
+        }
+    }
+}
+
+impl core::error::Error for FormatError {}
+
+/// Compresses `data` into the container format.
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let (code, bits) = huffman::encode(data);
+    let mut compressed = Vec::with_capacity(HEADER_LENGTH + bits.len());
+    compressed.extend_from_slice(&MAGIC);
+    compressed.push(VERSION);
+    compressed.extend_from_slice(code.lengths());
+    compressed.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    compressed.extend_from_slice(&crc32(data).to_le_bytes());
+    compressed.extend_from_slice(&bits);
+    compressed
+}
+
+/// Recovers the data [`compress`] was given, checking the header and the
+/// checksum along the way.
+pub fn decompress(compressed: &[u8]) -> Result<Vec<u8>, FormatError> {
+    if !compressed.starts_with(&MAGIC) {
+        if MAGIC.starts_with(compressed) {
+            return Err(FormatError::TruncatedHeader(compressed.len()));
+        }
+        return Err(FormatError::BadMagic);
+    }
+    if compressed.len() < HEADER_LENGTH {
+        return Err(FormatError::TruncatedHeader(compressed.len()));
+    }
+    let (header, bits) = compressed.split_at(HEADER_LENGTH);
+    let version = header[4];
+    if version != VERSION {
+        return Err(FormatError::UnsupportedVersion(version));
+    }
+    let lengths: [u8; 256] = header[5..261].try_into().unwrap();
+    let length = u64::from_le_bytes(header[261..269].try_into().unwrap());
+    let expected = u32::from_le_bytes(header[269..273].try_into().unwrap());
+
+    let code = HuffmanCode::from_lengths(lengths).map_err(|_| FormatError::InvalidCodeLengths)?;
+    let length = usize::try_from(length).map_err(|_| FormatError::CorruptData)?;
+    let data = code.decode(bits, length).map_err(|error| match error {
+        HuffmanError::TruncatedInput => FormatError::TruncatedData,
+        _ => FormatError::CorruptData,
+    })?;
+    let actual = crc32(&data);
+    if actual != expected {
+        return Err(FormatError::ChecksumMismatch { expected, actual });
+    }
+    Ok(data)
+}
+
+/// CRC-32 as used by zip and PNG.
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                0xedb8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::test_support::deepest_code_data;
+
+    fn create_data() -> Vec<u8> {
+        b"it was the best of times, it was the worst of times".repeat(20)
+    }
+
+    #[test]
+    fn it_should_round_trip_through_the_container() {
+        for data in [create_data(), vec![], vec![42], (0..=255).collect()] {
+            let compressed = compress(&data);
+            assert_eq!(MAGIC, compressed[..4]);
+            assert_eq!(data, decompress(&compressed).unwrap());
+        }
+        assert!(compress(&create_data()).len() < create_data().len());
+    }
+
+    #[test]
+    fn it_should_round_trip_codes_of_the_maximum_length() {
+        let data = deepest_code_data();
+        let compressed = compress(&data);
+        assert_eq!(
+            Some(&huffman::MAX_CODE_LENGTH),
+            compressed[5..261].iter().max()
+        );
+        assert_eq!(data, decompress(&compressed).unwrap());
+    }
+
+    #[test]
+    fn it_should_match_the_standard_crc32() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+        assert_eq!(0, crc32(b""));
+    }
+
+    #[test]
+    fn it_should_report_truncated_files() {
+        let compressed = compress(&create_data());
+        assert_eq!(
+            Err(FormatError::TruncatedHeader(2)),
+            decompress(&compressed[..2])
+        );
+        assert_eq!(
+            Err(FormatError::TruncatedHeader(100)),
+            decompress(&compressed[..100])
+        );
+        assert_eq!(
+            Err(FormatError::TruncatedData),
+            decompress(&compressed[..compressed.len() - 10])
+        );
+    }
+
+    #[test]
+    fn it_should_report_corrupt_files() {
+        let compressed = compress(&create_data());
+        assert_eq!(
+            Err(FormatError::BadMagic),
+            decompress(b"PK\x03\x04 not ours")
+        );
+
+        let mut wrong_version = compressed.clone();
+        wrong_version[4] = 9;
+        assert_eq!(
+            Err(FormatError::UnsupportedVersion(9)),
+            decompress(&wrong_version)
+        );
+
+        let mut bad_lengths = compressed.clone();
+        bad_lengths[5..261].fill(1);
+        assert_eq!(
+            Err(FormatError::InvalidCodeLengths),
+            decompress(&bad_lengths)
+        );
+
+        let mut flipped = compressed.clone();
+        let last = flipped.len() - 20;
+        flipped[last] ^= 0xff;
+        assert!(matches!(
+            decompress(&flipped),
+            Err(FormatError::ChecksumMismatch { .. } | FormatError::CorruptData)
+        ));
+
+        let mut bad_checksum = compressed;
+        bad_checksum[269] ^= 1;
+        assert!(matches!(
+            decompress(&bad_checksum),
+            Err(FormatError::ChecksumMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,395 @@
+use alloc::{vec, vec::Vec};
+use core::{cmp::Reverse, fmt};
+
+use crate::heaps::d_heap::DHeap;
+
+/// Longest code [`code_lengths`] assigns. When the Huffman tree is deeper the
+/// frequencies are flattened and the tree rebuilt until it fits.
+pub const MAX_CODE_LENGTH: u8 = 32;
+
+/// Branch factor of the heap the tree is built with.
+const B: usize = 4;
+
+/// Why a code could not be built or a bitstream could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HuffmanError {
+    /// The byte has no code, so it cannot be encoded.
+    MissingSymbol(u8),
+    /// The code lengths are too long or oversubscribed, so they do not describe
+    /// a prefix code.
+    InvalidCodeLengths,
+    /// The bitstream ended before all of the symbols were decoded.
+    TruncatedInput,
+    /// The bitstream holds a bit sequence that is not a code in use.
+    InvalidCode,
+}
+
+impl fmt::Display for HuffmanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HuffmanError::MissingSymbol(symbol) => write!(f, "byte {symbol:#04x} has no code"),
+            HuffmanError::InvalidCodeLengths => write!(f, "code lengths do not form a prefix code"),
+            HuffmanError::TruncatedInput => write!(f, "bitstream ended early"),
+            HuffmanError::InvalidCode => write!(f, "bitstream contains an unused code"),
+        }
+    }
+}
+
+impl core::error::Error for HuffmanError {}
+
+/// Counts how often each byte value occurs in `data`.
+pub fn byte_frequencies(data: &[u8]) -> [u64; 256] {
+    let mut frequencies = [0; 256];
+    for &byte in data {
+        frequencies[byte as usize] += 1;
+    }
+    frequencies
+}
+
+/// Huffman code lengths for the symbols `0..frequencies.len()`, none longer
+/// than [`MAX_CODE_LENGTH`]. Symbols that never occur get length 0, and a lone
+/// symbol gets length 1 so it still has a code.
+pub fn code_lengths(frequencies: &[u64]) -> Vec<u8> {
+    let mut frequencies = frequencies.to_vec();
+    loop {
+        let depths = tree_depths(&frequencies);
+        if depths
+            .iter()
+            .all(|&depth| depth <= MAX_CODE_LENGTH as usize)
+        {
+            return depths.into_iter().map(|depth| depth as u8).collect();
+        }
+        // halving evens out the weights, and once they are all 1 the tree is
+        // balanced
+        for frequency in frequencies.iter_mut().filter(|f| **f > 0) {
+            *frequency = (*frequency >> 1) | 1;
+        }
+    }
+}
+
+/// Depth of each symbol's leaf in the Huffman tree, built by repeatedly
+/// merging the two lightest nodes taken from a min-heap.
+fn tree_depths(frequencies: &[u64]) -> Vec<usize> {
+    let mut depths = vec![0; frequencies.len()];
+    let leaves: Vec<usize> = (0..frequencies.len())
+        .filter(|&symbol| frequencies[symbol] > 0)
+        .collect();
+    if leaves.len() == 1 {
+        depths[leaves[0]] = 1;
+        return depths;
+    }
+
+    // nodes are numbered leaves first, and each parent after its children
+    let mut heap: DHeap<Reverse<(u64, usize)>, B> = DHeap::with_capacity(leaves.len());
+    for (node, &symbol) in leaves.iter().enumerate() {
+        heap.insert(Reverse((frequencies[symbol], node)));
+    }
+    let mut parents = vec![usize::MAX; leaves.len()];
+    while heap.size() > 1 {
+        let Reverse((first_weight, first)) = heap.top().unwrap();
+        let Reverse((second_weight, second)) = heap.top().unwrap();
+        let parent = parents.len();
+        parents.push(usize::MAX);
+        parents[first] = parent;
+        parents[second] = parent;
+        heap.insert(Reverse((
+            first_weight.saturating_add(second_weight),
+            parent,
+        )));
+    }
+
+    let mut node_depths = vec![0; parents.len()];
+    for node in (0..parents.len()).rev() {
+        if parents[node] != usize::MAX {
+            node_depths[node] = node_depths[parents[node]] + 1;
+        }
+    }
+    for (node, &symbol) in leaves.iter().enumerate() {
+        depths[symbol] = node_depths[node];
+    }
+    depths
+}
+
+/// Canonical codes for `lengths`: shorter codes come first, and codes of the
+/// same length are consecutive in symbol order. A symbol of length 0 gets no
+/// code.
+pub fn canonical_codes(lengths: &[u8]) -> Result<Vec<u32>, HuffmanError> {
+    let mut length_counts = [0u64; MAX_CODE_LENGTH as usize + 1];
+    for &length in lengths {
+        if length > MAX_CODE_LENGTH {
+            return Err(HuffmanError::InvalidCodeLengths);
+        }
+        length_counts[length as usize] += 1;
+    }
+    length_counts[0] = 0;
+    let kraft_sum: u64 = (1..=MAX_CODE_LENGTH)
+        .map(|length| length_counts[length as usize] << (MAX_CODE_LENGTH - length))
+        .sum();
+    if kraft_sum > 1 << MAX_CODE_LENGTH {
+        return Err(HuffmanError::InvalidCodeLengths);
+    }
+
+    let mut next_codes = [0u64; MAX_CODE_LENGTH as usize + 1];
+    let mut code = 0;
+    for length in 1..=MAX_CODE_LENGTH as usize {
+        code = (code + length_counts[length - 1]) << 1;
+        next_codes[length] = code;
+    }
+    Ok(lengths
+        .iter()
+        .map(|&length| {
+            if length == 0 {
+                return 0;
+            }
+            let code = next_codes[length as usize];
+            next_codes[length as usize] += 1;
+            code as u32
+        })
+        .collect())
+}
+
+/// A canonical prefix code over bytes.
+///
+/// Since the codes are canonical they can be rebuilt from the lengths alone,
+/// which is all a compressed stream needs to store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanCode {
+    lengths: [u8; 256],
+    codes: [u32; 256],
+}
+
+impl HuffmanCode {
+    pub fn from_frequencies(frequencies: &[u64; 256]) -> Self {
+        let lengths: [u8; 256] = code_lengths(frequencies).try_into().unwrap();
+        Self::from_lengths(lengths).expect("Huffman code lengths form a prefix code")
+    }
+
+    pub fn from_lengths(lengths: [u8; 256]) -> Result<Self, HuffmanError> {
+        let codes = canonical_codes(&lengths)?.try_into().unwrap();
+        Ok(HuffmanCode { lengths, codes })
+    }
+
+    pub fn lengths(&self) -> &[u8; 256] {
+        &self.lengths
+    }
+
+    /// The code for `symbol` and its length in bits, if it has one.
+    pub fn code(&self, symbol: u8) -> Option<(u32, u8)> {
+        let length = self.lengths[symbol as usize];
+        (length > 0).then_some((self.codes[symbol as usize], length))
+    }
+
+    /// Packs the codes for `data` most significant bit first, padding the
+    /// last byte with zeroes.
+    pub fn encode(&self, data: &[u8]) -> Result<Vec<u8>, HuffmanError> {
+        let mut writer = BitWriter::default();
+        for &symbol in data {
+            let (code, length) = self
+                .code(symbol)
+                .ok_or(HuffmanError::MissingSymbol(symbol))?;
+            writer.write(code, length);
+        }
+        Ok(writer.finish())
+    }
+
+    /// Decodes `symbols` bytes from `bits`, ignoring any padding after them.
+    pub fn decode(&self, bits: &[u8], symbols: usize) -> Result<Vec<u8>, HuffmanError> {
+        let decoder = Decoder::new(&self.lengths);
+        let mut reader = BitReader { bits, position: 0 };
+        // every code is at least a bit long, so a bogus count can't over-allocate
+        let mut decoded = Vec::with_capacity(symbols.min(bits.len() * 8));
+        for _ in 0..symbols {
+            decoded.push(decoder.decode(&mut reader)?);
+        }
+        Ok(decoded)
+    }
+}
+
+/// Builds a code from the byte frequencies of `data` and encodes it.
+pub fn encode(data: &[u8]) -> (HuffmanCode, Vec<u8>) {
+    let code = HuffmanCode::from_frequencies(&byte_frequencies(data));
+    let bits = code
+        .encode(data)
+        .expect("every byte of the data has a code");
+    (code, bits)
+}
+
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    pending: u64,
+    pending_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u32, length: u8) {
+        self.pending = (self.pending << length) | code as u64;
+        self.pending_bits += length as u32;
+        while self.pending_bits >= 8 {
+            self.pending_bits -= 8;
+            self.bytes.push((self.pending >> self.pending_bits) as u8);
+        }
+        self.pending &= (1 << self.pending_bits) - 1;
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.pending_bits > 0 {
+            self.bytes
+                .push((self.pending << (8 - self.pending_bits)) as u8);
+        }
+        self.bytes
+    }
+}
+
+struct BitReader<'a> {
+    bits: &'a [u8],
+    position: usize,
+}
+
+impl BitReader<'_> {
+    fn read(&mut self) -> Option<u32> {
+        let byte = self.bits.get(self.position / 8)?;
+        let bit = (byte >> (7 - self.position % 8)) & 1;
+        self.position += 1;
+        Some(bit as u32)
+    }
+}
+
+/// Decodes canonical codes one bit at a time: the codes of each length are
+/// consecutive, so a code is in use if it falls within its length's range.
+struct Decoder {
+    /// Symbols sorted by code length, then by value.
+    symbols: Vec<u8>,
+    length_counts: [u64; MAX_CODE_LENGTH as usize + 1],
+    max_length: usize,
+}
+
+impl Decoder {
+    fn new(lengths: &[u8; 256]) -> Self {
+        let mut symbols: Vec<u8> = (0..=255).filter(|&s| lengths[s as usize] > 0).collect();
+        symbols.sort_by_key(|&symbol| lengths[symbol as usize]);
+        let mut length_counts = [0; MAX_CODE_LENGTH as usize + 1];
+        for &symbol in &symbols {
+            length_counts[lengths[symbol as usize] as usize] += 1;
+        }
+        let max_length = symbols.last().map_or(0, |&s| lengths[s as usize] as usize);
+        Decoder {
+            symbols,
+            length_counts,
+            max_length,
+        }
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<u8, HuffmanError> {
+        // code, first code and first symbol index for the current length, in
+        // u64 since past the last 32 bit code `first_code + count` is 2^32
+        let mut code = 0;
+        let mut first_code = 0;
+        let mut first_index = 0;
+        for &count in &self.length_counts[1..=self.max_length] {
+            code |= u64::from(reader.read().ok_or(HuffmanError::TruncatedInput)?);
+            if code < first_code + count {
+                return Ok(self.symbols[(first_index + code - first_code) as usize]);
+            }
+            first_index += count;
+            first_code = (first_code + count) << 1;
+            code <<= 1;
+        }
+        Err(HuffmanError::InvalidCode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compression::test_support::deepest_code_data;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn round_trip(data: &[u8]) {
+        let (code, bits) = encode(data);
+        assert_eq!(data, code.decode(&bits, data.len()).unwrap());
+        let rebuilt = HuffmanCode::from_lengths(*code.lengths()).unwrap();
+        assert_eq!(code, rebuilt);
+    }
+
+    #[test]
+    fn it_should_round_trip_random_input() {
+        let mut rng = StdRng::seed_from_u64(49);
+        for size in [1, 2, 17, 1_000, 20_000] {
+            // skewed so the codes have a spread of lengths
+            let data: Vec<u8> = (0..size)
+                .map(|_| (rng.gen_range(0..64u32).pow(2) / 16) as u8)
+                .collect();
+            round_trip(&data);
+        }
+    }
+
+    #[test]
+    fn it_should_round_trip_edge_cases() {
+        round_trip(&[]);
+        round_trip(&[7; 1_000]);
+        round_trip(&(0..=255).collect::<Vec<u8>>());
+        round_trip(&[0, 255]);
+
+        let (code, bits) = encode(&[]);
+        assert!(bits.is_empty());
+        assert_eq!(None, code.code(0));
+        let (code, bits) = encode(&[7; 9]);
+        assert_eq!(Some((0, 1)), code.code(7));
+        assert_eq!(vec![0, 0], bits);
+    }
+
+    #[test]
+    fn it_should_assign_canonical_codes_to_the_shortest_lengths() {
+        // the classic example: a:45 b:13 c:12 d:16 e:9 f:5
+        let lengths = code_lengths(&[45, 13, 12, 16, 9, 5]);
+        assert_eq!(vec![1, 3, 3, 3, 4, 4], lengths);
+        let codes = canonical_codes(&lengths).unwrap();
+        assert_eq!(vec![0b0, 0b100, 0b101, 0b110, 0b1110, 0b1111], codes);
+    }
+
+    #[test]
+    fn it_should_limit_code_lengths() {
+        // Fibonacci weights give the deepest possible tree
+        let mut frequencies = vec![1u64, 1];
+        while frequencies.len() < 48 {
+            let next = frequencies[frequencies.len() - 1] + frequencies[frequencies.len() - 2];
+            frequencies.push(next);
+        }
+        let lengths = code_lengths(&frequencies);
+        assert!(lengths
+            .iter()
+            .all(|&length| (1..=MAX_CODE_LENGTH).contains(&length)));
+        assert!(canonical_codes(&lengths).is_ok());
+    }
+
+    #[test]
+    fn it_should_round_trip_codes_of_the_maximum_length() {
+        let data = deepest_code_data();
+        let (code, _) = encode(&data);
+        assert_eq!(Some(&MAX_CODE_LENGTH), code.lengths().iter().max());
+        round_trip(&data);
+    }
+
+    #[test]
+    fn it_should_reject_bad_lengths_and_bitstreams() {
+        let mut lengths = [0; 256];
+        lengths[..3].copy_from_slice(&[1, 1, 1]);
+        assert_eq!(
+            Err(HuffmanError::InvalidCodeLengths),
+            HuffmanCode::from_lengths(lengths)
+        );
+
+        let (code, bits) = encode(b"abracadabra");
+        assert_eq!(
+            Err(HuffmanError::TruncatedInput),
+            code.decode(&bits[..1], 11)
+        );
+        assert_eq!(Err(HuffmanError::MissingSymbol(b'z')), code.encode(b"z"));
+        let (code, _) = encode(b"aaa");
+        assert_eq!(
+            Err(HuffmanError::InvalidCode),
+            code.decode(&[0b1000_0000], 1)
+        );
+    }
+}
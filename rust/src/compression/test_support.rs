@@ -0,0 +1,17 @@
+//! Helpers shared by the compression test modules.
+
+/// About 9.2 MB in which the bytes `0..33` occur as often as the first 33
+/// Fibonacci numbers. That skew makes the Huffman tree a chain, so the two
+/// rarest bytes get codes of exactly
+/// [`MAX_CODE_LENGTH`](super::huffman::MAX_CODE_LENGTH) bits.
+pub(crate) fn deepest_code_data() -> Vec<u8> {
+    let mut frequencies = vec![1, 1];
+    while frequencies.len() < 33 {
+        let next = frequencies[frequencies.len() - 1] + frequencies[frequencies.len() - 2];
+        frequencies.push(next);
+    }
+    (0..33u8)
+        .zip(frequencies)
+        .flat_map(|(byte, frequency)| core::iter::repeat_n(byte, frequency))
+        .collect()
+}
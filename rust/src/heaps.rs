@@ -1,3 +1,34 @@
+mod array_d_heap;
+#[cfg(feature = "std")]
+pub mod concurrent;
 pub mod d_heap;
+#[cfg(feature = "std")]
 pub mod d_heap_arc;
+#[cfg(feature = "std")]
 pub mod d_way_heap_clone;
+#[cfg(feature = "std")]
+pub mod delay;
+#[cfg(feature = "std")]
+pub mod external;
+pub mod fibonacci;
+mod heap_id;
+pub mod merge;
+mod min_max;
+#[cfg(feature = "std")]
+pub mod multi_queue;
+pub mod pairing;
+pub mod persistent;
+pub mod radix;
+pub(crate) mod render;
+pub mod slice;
+pub mod sort;
+mod stats;
+#[cfg(test)]
+mod test_support;
+mod top_k;
+
+pub use array_d_heap::{ArrayDHeap, ArrayDrain};
+pub use min_max::{Evict, MinMaxHeap};
+pub use render::Render;
+pub use stats::HeapStats;
+pub use top_k::TopK;
@@ -0,0 +1,448 @@
+use core::{
+    mem::{replace, MaybeUninit},
+    ptr, slice,
+};
+
+use super::d_heap::{bubble_up_by, get_parent_index, push_down_by};
+#[cfg(feature = "instrumented")]
+use super::stats::HeapStats;
+use super::stats::StatsRecorder;
+
+/// A `B`-ary max-heap holding at most `N` elements in an inline array, so it
+/// never allocates.
+///
+/// Only the first `len` slots are initialised. Sifting goes through the same
+/// hole based routines as [`DHeap`](super::d_heap::DHeap) on that initialised
+/// prefix.
+///
+/// `len` is only changed while no sift is in progress, so a panicking `Ord`
+/// implementation gives the same guarantees as for `DHeap`: each element is
+/// still dropped exactly once.
+pub struct ArrayDHeap<T: Ord, const B: usize, const N: usize> {
+    elements: [MaybeUninit<T>; N],
+    len: usize,
+    stats: StatsRecorder,
+}
+
+impl<T: Ord, const B: usize, const N: usize> ArrayDHeap<T, B, N> {
+    pub const fn new() -> Self {
+        ArrayDHeap {
+            elements: [const { MaybeUninit::uninit() }; N],
+            len: 0,
+            stats: StatsRecorder::new(),
+        }
+    }
+
+    fn as_slice(&self) -> &[T] {
+        // SAFETY: the first `len` slots are initialised
+        unsafe { slice::from_raw_parts(self.elements.as_ptr().cast(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        self.parts_mut().0
+    }
+
+    /// The initialised elements alongside the stats recorder, so a sift can
+    /// record while it borrows the elements.
+    fn parts_mut(&mut self) -> (&mut [T], &mut StatsRecorder) {
+        // SAFETY: the first `len` slots are initialised
+        let data =
+            unsafe { slice::from_raw_parts_mut(self.elements.as_mut_ptr().cast(), self.len) };
+        (data, &mut self.stats)
+    }
+
+    /// Inserts `element`, handing it back as `Err` if the heap is full.
+    pub fn insert(&mut self, element: T) -> Result<(), T> {
+        if self.len == N {
+            return Err(element);
+        }
+        let index = self.len;
+        self.elements[index].write(element);
+        self.len += 1;
+        self.bubble_up(index);
+        Ok(())
+    }
+
+    pub fn top(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        // SAFETY: the slot was initialised and is now outside of `len`, so it
+        // is read exactly once
+        let last = unsafe { self.elements[self.len].assume_init_read() };
+        if self.len == 0 {
+            return Some(last);
+        }
+        let top = replace(&mut self.as_mut_slice()[0], last);
+        self.push_down(0);
+        Some(top)
+    }
+
+    /// Drops every element.
+    pub fn clear(&mut self) {
+        let data: *mut [T] = self.as_mut_slice();
+        // forget the elements first, so a panicking `Drop` leaks the rest
+        // instead of dropping them twice
+        self.len = 0;
+        // SAFETY: the slots were initialised and are now outside of `len`
+        unsafe { ptr::drop_in_place(data) }
+    }
+
+    /// Keeps only the elements matching `predicate`, heapifying once
+    /// afterwards if anything was removed.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut predicate: F) {
+        let size = self.len;
+        let mut compaction = Compaction {
+            heap: self,
+            size,
+            read: 0,
+            kept: 0,
+        };
+        while compaction.read < size {
+            let base = compaction.heap.elements.as_mut_ptr();
+            // SAFETY: slots from `read` to `size` are still initialised
+            let current = unsafe { base.add(compaction.read) };
+            if predicate(unsafe { (*current).assume_init_ref() }) {
+                if compaction.read != compaction.kept {
+                    // SAFETY: `kept` is before `read`, and its slot was moved
+                    // out of or dropped already
+                    unsafe { ptr::copy_nonoverlapping(current, base.add(compaction.kept), 1) };
+                }
+                compaction.kept += 1;
+                compaction.read += 1;
+            } else {
+                compaction.read += 1;
+                // SAFETY: the slot is past `read` now, so it is dropped once
+                unsafe { (*current).assume_init_drop() };
+            }
+        }
+        drop(compaction);
+        if self.len < size {
+            self.heapify();
+        }
+    }
+
+    /// Removes every element, yielding them in heap order rather than sorted
+    /// order. Elements the iterator doesn't yield are dropped with it.
+    pub fn drain(&mut self) -> ArrayDrain<'_, T> {
+        let size = self.len;
+        self.len = 0;
+        ArrayDrain {
+            slots: self.elements[..size].iter_mut(),
+        }
+    }
+
+    fn heapify(&mut self) {
+        if self.len < 2 {
+            return;
+        }
+        for index in (0..=get_parent_index(self.len - 1, B)).rev() {
+            self.push_down(index);
+        }
+    }
+
+    fn push_down(&mut self, index: usize) {
+        let (data, stats) = self.parts_mut();
+        let sift = push_down_by::<T, _, B>(data, index, &mut stats.less_than());
+        stats.record_sift(index, sift, B);
+    }
+
+    fn bubble_up(&mut self, index: usize) {
+        let (data, stats) = self.parts_mut();
+        let sift = bubble_up_by::<T, _, B>(data, index, &mut stats.less_than());
+        stats.record_sift(index, sift, B);
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.as_slice().first()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn size(&self) -> usize {
+        self.len
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub fn stats(&self) -> HeapStats {
+        self.stats.stats()
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    pub fn contains(&self, element: &T) -> bool {
+        self.as_slice().contains(element)
+    }
+}
+
+/// Tracks [`ArrayDHeap::retain`] while it compacts the kept elements to the
+/// front. Dropping it sets `len`, also when the predicate or an element's
+/// `Drop` panics: the unvisited elements are then moved after the kept ones,
+/// so every initialised slot stays within `len`.
+struct Compaction<'a, T: Ord, const B: usize, const N: usize> {
+    heap: &'a mut ArrayDHeap<T, B, N>,
+    size: usize,
+    read: usize,
+    kept: usize,
+}
+
+impl<T: Ord, const B: usize, const N: usize> Drop for Compaction<'_, T, B, N> {
+    fn drop(&mut self) {
+        let unvisited = self.size - self.read;
+        if self.read != self.kept {
+            let base = self.heap.elements.as_mut_ptr();
+            // SAFETY: moves the still initialised slots from `read` to
+            // `size` right after the kept ones; the ranges may overlap
+            unsafe { ptr::copy(base.add(self.read), base.add(self.kept), unvisited) };
+        }
+        self.heap.len = self.kept + unvisited;
+    }
+}
+
+/// The iterator returned by [`ArrayDHeap::drain`].
+pub struct ArrayDrain<'a, T> {
+    slots: slice::IterMut<'a, MaybeUninit<T>>,
+}
+
+impl<T> Iterator for ArrayDrain<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        // SAFETY: the heap gave up the drained slots, each is read once
+        self.slots
+            .next()
+            .map(|slot| unsafe { slot.assume_init_read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.slots.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for ArrayDrain<'_, T> {}
+
+impl<T> Drop for ArrayDrain<'_, T> {
+    fn drop(&mut self) {
+        for slot in &mut self.slots {
+            // SAFETY: the slot was not yielded, so it is dropped once
+            unsafe { slot.assume_init_drop() }
+        }
+    }
+}
+
+impl<T: Ord, const B: usize, const N: usize> Default for ArrayDHeap<T, B, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord, const B: usize, const N: usize> Drop for ArrayDHeap<T, B, N> {
+    fn drop(&mut self) {
+        // SAFETY: drops each initialised element once; the slots are never
+        // read again
+        unsafe { ptr::drop_in_place(self.as_mut_slice()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::heaps::test_support::{
+        self, panics_on_comparison, priority_queue_tests, TestHeap, Tracked,
+    };
+
+    impl TestHeap for ArrayDHeap<u64, 3, 10> {
+        fn insert(&mut self, element: u64) {
+            ArrayDHeap::insert(self, element).unwrap();
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            ArrayDHeap::top(self)
+        }
+
+        fn peek(&self) -> Option<u64> {
+            ArrayDHeap::peek(self).copied()
+        }
+
+        fn size(&self) -> usize {
+            ArrayDHeap::size(self)
+        }
+    }
+
+    fn create_heap() -> ArrayDHeap<u64, 3, 10> {
+        test_support::create_heap()
+    }
+
+    priority_queue_tests!(ArrayDHeap<u64, 3, 10>);
+
+    #[test]
+    fn it_should_hand_back_the_element_when_full() {
+        let mut heap = create_heap();
+        heap.insert(1).unwrap();
+        heap.insert(14).unwrap();
+        assert!(heap.is_full());
+        assert_eq!(Err(2), heap.insert(2));
+        assert_eq!(Some(&14), heap.peek());
+        assert_eq!(Some(14), heap.top());
+        assert_eq!(Ok(()), heap.insert(2));
+        assert_eq!(10, heap.size());
+    }
+
+    #[test]
+    fn it_should_retain_matching_elements_in_heap_order() {
+        let mut heap = create_heap();
+        heap.retain(|&number| number % 2 == 1);
+        assert_eq!(5, heap.size());
+        for number in [9, 9, 7, 5, 3] {
+            assert_eq!(Some(number), heap.top());
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_drain_and_clear() {
+        let mut heap = create_heap();
+        let mut drained: Vec<u64> = heap.drain().collect();
+        drained.sort_unstable();
+        assert_eq!(vec![3, 5, 7, 8, 8, 9, 9, 10], drained);
+        assert!(heap.is_empty());
+
+        heap.insert(1).unwrap();
+        heap.insert(2).unwrap();
+        heap.clear();
+        assert_eq!(None, heap.top());
+        assert_eq!(Ok(()), heap.insert(4));
+        assert_eq!(Some(4), heap.top());
+    }
+
+    #[test]
+    fn it_should_drop_each_element_exactly_once_when_retaining_and_draining() {
+        let drops = Rc::new(Cell::new(0));
+        let mut heap: ArrayDHeap<Tracked, 2, 8> = ArrayDHeap::new();
+        for number in 0..8 {
+            assert!(heap.insert(Tracked::new(number, &drops)).is_ok());
+        }
+        heap.retain(|tracked| tracked.value % 3 != 0);
+        assert_eq!(3, drops.get());
+        assert_eq!(7, heap.top().unwrap().value);
+        assert_eq!(4, drops.get());
+
+        let mut drain = heap.drain();
+        assert_eq!(4, drain.len());
+        drop(drain.next());
+        assert_eq!(5, drops.get());
+        drop(drain);
+        assert_eq!(8, drops.get());
+        assert!(heap.is_empty());
+
+        heap.insert(Tracked::new(1, &drops)).ok().unwrap();
+        heap.clear();
+        assert_eq!(9, drops.get());
+        drop(heap);
+        assert_eq!(9, drops.get());
+    }
+
+    #[test]
+    fn it_should_keep_the_unvisited_elements_when_retain_panics() {
+        let drops = Rc::new(Cell::new(0));
+        let mut heap: ArrayDHeap<Tracked, 2, 8> = ArrayDHeap::new();
+        for number in 0..8 {
+            assert!(heap.insert(Tracked::new(number, &drops)).is_ok());
+        }
+        let mut visited = 0;
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            heap.retain(|tracked| {
+                visited += 1;
+                assert!(visited < 5, "predicate failed");
+                tracked.value % 2 == 0
+            })
+        }));
+        assert!(result.is_err());
+        // the elements rejected before the panic are dropped, the rest kept
+        let dropped = drops.get();
+        assert_eq!(8, heap.size() + dropped);
+        drop(heap);
+        assert_eq!(8, drops.get());
+    }
+
+    #[test]
+    fn it_should_work_with_no_capacity() {
+        let mut heap: ArrayDHeap<u64, 2, 0> = ArrayDHeap::new();
+        assert_eq!(Err(1), heap.insert(1));
+        assert_eq!(None, heap.top());
+        assert_eq!(0, heap.capacity());
+    }
+
+    #[test]
+    fn it_should_drop_each_element_exactly_once() {
+        let drops = Rc::new(Cell::new(0));
+        let mut heap: ArrayDHeap<Tracked, 2, 4> = ArrayDHeap::new();
+        for number in [3, 1, 4, 1] {
+            assert!(heap.insert(Tracked::new(number, &drops)).is_ok());
+        }
+        let rejected = heap.insert(Tracked::new(5, &drops)).unwrap_err();
+        assert_eq!(0, drops.get());
+        drop(rejected);
+        assert_eq!(1, drops.get());
+
+        assert_eq!(4, heap.top().unwrap().value);
+        assert_eq!(2, drops.get());
+        drop(heap);
+        assert_eq!(5, drops.get());
+    }
+
+    #[test]
+    fn it_should_not_leak_heap_allocated_elements() {
+        // run under `cargo miri test` to catch leaks, double frees and reads
+        // of uninitialised slots
+        let mut heap: ArrayDHeap<Box<u64>, 3, 6> = ArrayDHeap::new();
+        for number in [9, 2, 7, 4, 8, 1] {
+            heap.insert(Box::new(number)).unwrap();
+        }
+        assert_eq!(Some(Box::new(9)), heap.top());
+        assert_eq!(Some(Box::new(8)), heap.top());
+        heap.insert(Box::new(5)).unwrap();
+        assert!(heap.contains(&Box::new(5)));
+    }
+
+    #[test]
+    fn it_should_drop_each_element_exactly_once_when_a_comparison_panics() {
+        for nth in 1.. {
+            let drops = Rc::new(Cell::new(0));
+            let mut heap: ArrayDHeap<Tracked, 2, 16> = ArrayDHeap::new();
+            for number in 0..15 {
+                assert!(heap.insert(Tracked::new((number * 4) % 15, &drops)).is_ok());
+            }
+            let panicked = panics_on_comparison(nth, || {
+                assert!(heap.insert(Tracked::new(15, &drops)).is_ok());
+                heap.top()
+            });
+            if !panicked {
+                assert!(nth > 4);
+                break;
+            }
+            // a panic in insert drops nothing, one in top drops the old top
+            let expected = if heap.size() == 16 { 0 } else { 1 };
+            assert_eq!(expected, drops.get());
+            let size = heap.size();
+            drop(heap);
+            assert_eq!(size + expected, drops.get());
+        }
+    }
+}
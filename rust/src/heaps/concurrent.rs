@@ -0,0 +1,265 @@
+use std::{
+    fmt,
+    sync::{Condvar, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+use super::d_heap::DHeap;
+
+/// Why [`ConcurrentDHeap::pop_timeout`] came back without an element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PopTimeoutError {
+    Timeout,
+    Closed,
+}
+
+impl fmt::Display for PopTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopTimeoutError::Timeout => write!(f, "timed out waiting for an element"),
+            PopTimeoutError::Closed => write!(f, "heap is closed and empty"),
+        }
+    }
+}
+
+impl std::error::Error for PopTimeoutError {}
+
+struct State<T: Ord, const B: usize> {
+    heap: DHeap<T, B>,
+    closed: bool,
+}
+
+/// A [`DHeap`] behind a mutex that producer and consumer threads can share,
+/// typically through an `Arc`.
+///
+/// Consumers can block until an element arrives. Once [`close`] is called no
+/// more elements are accepted, and blocked consumers wake up and drain what is
+/// left before seeing the heap as finished.
+///
+/// [`close`]: ConcurrentDHeap::close
+pub struct ConcurrentDHeap<T: Ord, const B: usize = 4> {
+    state: Mutex<State<T, B>>,
+    available: Condvar,
+}
+
+impl<T: Ord, const B: usize> ConcurrentDHeap<T, B> {
+    pub fn new(elements: Vec<T>) -> Self {
+        ConcurrentDHeap {
+            state: Mutex::new(State {
+                heap: DHeap::new(elements),
+                closed: false,
+            }),
+            available: Condvar::new(),
+        }
+    }
+
+    /// Inserts `element`, handing it back if the heap has been closed.
+    pub fn insert(&self, element: T) -> Result<(), T> {
+        let mut state = self.lock();
+        if state.closed {
+            return Err(element);
+        }
+        state.heap.insert(element);
+        drop(state);
+        self.available.notify_one();
+        Ok(())
+    }
+
+    /// Removes the top element without waiting.
+    pub fn try_pop(&self) -> Option<T> {
+        self.lock().heap.top()
+    }
+
+    /// Removes the top element, blocking until one is available. Returns
+    /// `None` once the heap is closed and empty.
+    pub fn pop_wait(&self) -> Option<T> {
+        let mut state = self.lock();
+        loop {
+            if let Some(element) = state.heap.top() {
+                return Some(element);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .available
+                .wait(state)
+                .expect("ConcurrentDHeap lock poisoned");
+        }
+    }
+
+    /// Like [`pop_wait`](Self::pop_wait) but gives up after `timeout`. A
+    /// timeout too long to have a deadline, like `Duration::MAX`, waits forever.
+    pub fn pop_timeout(&self, timeout: Duration) -> Result<T, PopTimeoutError> {
+        let Some(deadline) = Instant::now().checked_add(timeout) else {
+            return self.pop_wait().ok_or(PopTimeoutError::Closed);
+        };
+        let mut state = self.lock();
+        loop {
+            if let Some(element) = state.heap.top() {
+                return Ok(element);
+            }
+            if state.closed {
+                return Err(PopTimeoutError::Closed);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(PopTimeoutError::Timeout);
+            }
+            state = self
+                .available
+                .wait_timeout(state, deadline - now)
+                .expect("ConcurrentDHeap lock poisoned")
+                .0;
+        }
+    }
+
+    /// Stops accepting new elements and wakes every blocked consumer.
+    pub fn close(&self) {
+        self.lock().closed = true;
+        self.available.notify_all();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.lock().closed
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().heap.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.lock().heap.size()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State<T, B>> {
+        self.state.lock().expect("ConcurrentDHeap lock poisoned")
+    }
+}
+
+impl<T: Ord, const B: usize> Default for ConcurrentDHeap<T, B> {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    use crate::heaps::test_support::{self, priority_queue_tests, TestHeap};
+
+    impl TestHeap for ConcurrentDHeap<u64, 3> {
+        fn insert(&mut self, element: u64) {
+            ConcurrentDHeap::insert(self, element).unwrap();
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            self.try_pop()
+        }
+
+        fn peek(&self) -> Option<u64> {
+            self.lock().heap.peek().copied()
+        }
+
+        fn size(&self) -> usize {
+            ConcurrentDHeap::size(self)
+        }
+    }
+
+    fn create_heap() -> ConcurrentDHeap<u64, 3> {
+        test_support::create_heap()
+    }
+
+    priority_queue_tests!(ConcurrentDHeap<u64, 3>);
+
+    #[test]
+    fn it_should_time_out_popping_an_empty_heap() {
+        let heap: ConcurrentDHeap<u64, 3> = ConcurrentDHeap::default();
+        assert!(heap.is_empty());
+        assert_eq!(
+            Err(PopTimeoutError::Timeout),
+            heap.pop_timeout(Duration::from_millis(10))
+        );
+    }
+
+    #[test]
+    fn it_should_wait_without_a_deadline_for_huge_timeouts() {
+        let heap: Arc<ConcurrentDHeap<u64>> = Arc::new(ConcurrentDHeap::default());
+        let consumer = {
+            let heap = heap.clone();
+            thread::spawn(move || {
+                (
+                    heap.pop_timeout(Duration::MAX),
+                    heap.pop_timeout(Duration::MAX),
+                )
+            })
+        };
+        heap.insert(4).unwrap();
+        heap.close();
+        assert_eq!(
+            (Ok(4), Err(PopTimeoutError::Closed)),
+            consumer.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn it_should_drain_and_reject_inserts_after_close() {
+        let heap = create_heap();
+        heap.close();
+        assert!(heap.is_closed());
+        assert_eq!(Err(11), heap.insert(11));
+        assert_eq!(8, std::iter::from_fn(|| heap.pop_wait()).count());
+        assert_eq!(None, heap.pop_wait());
+        assert_eq!(
+            Err(PopTimeoutError::Closed),
+            heap.pop_timeout(Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn it_should_wake_blocked_consumers_on_insert_and_close() {
+        let heap: Arc<ConcurrentDHeap<u64>> = Arc::new(ConcurrentDHeap::default());
+        let consumer = {
+            let heap = heap.clone();
+            thread::spawn(move || (heap.pop_wait(), heap.pop_wait()))
+        };
+        heap.insert(4).unwrap();
+        heap.close();
+        assert_eq!((Some(4), None), consumer.join().unwrap());
+    }
+
+    #[test]
+    fn it_should_deliver_every_element_once_under_contention() {
+        let producers = 4;
+        let per_producer = 5_000u64;
+        let heap: Arc<ConcurrentDHeap<u64>> = Arc::new(ConcurrentDHeap::default());
+        let producer_handles: Vec<_> = (0..producers)
+            .map(|producer| {
+                let heap = heap.clone();
+                thread::spawn(move || {
+                    for n in 0..per_producer {
+                        heap.insert(producer * per_producer + n).unwrap();
+                    }
+                })
+            })
+            .collect();
+        let consumer_handles: Vec<_> = (0..4)
+            .map(|_| {
+                let heap = heap.clone();
+                thread::spawn(move || std::iter::from_fn(|| heap.pop_wait()).collect::<Vec<_>>())
+            })
+            .collect();
+        for handle in producer_handles {
+            handle.join().unwrap();
+        }
+        heap.close();
+        let mut received: Vec<u64> = consumer_handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        received.sort();
+        assert_eq!((0..producers * per_producer).collect::<Vec<_>>(), received);
+    }
+}
@@ -1,22 +1,87 @@
-use std::{
-    mem::{swap, ManuallyDrop},
+use alloc::{
+    string::String,
+    vec::{Drain, Vec},
+};
+use core::{
+    cmp::Ordering,
+    fmt,
+    mem::{replace, swap, ManuallyDrop},
+    ops::{Deref, DerefMut},
     ptr,
 };
 
+use super::render::{self, Render};
+#[cfg(feature = "instrumented")]
+use super::stats::HeapStats;
+use super::stats::StatsRecorder;
+
+/// A `B`-ary max-heap.
+///
+/// # Panic safety
+///
+/// If `T`'s `Ord` implementation panics part way through a sift, every element
+/// is still owned by the heap exactly once: nothing is dropped twice or
+/// leaked. The only exception is the element [`top`](DHeap::top) was about to
+/// return, which is dropped while unwinding. After such a panic the elements
+/// may no longer be in heap order, but the heap remains safe to use and drop.
 pub struct DHeap<T: Ord + PartialEq, const B: usize> {
     elements: Vec<T>,
+    stats: StatsRecorder,
+}
+
+/// Mutable access to the top element of a [`DHeap`], created by
+/// [`DHeap::peek_mut`]. The heap order is restored when the guard is dropped.
+pub struct PeekMut<'a, T: Ord + PartialEq, const B: usize> {
+    heap: &'a mut DHeap<T, B>,
+    sift: bool,
+}
+
+impl<T: Ord, const B: usize> PeekMut<'_, T, B> {
+    /// Removes the peeked element from the heap and returns it.
+    pub fn pop(mut this: Self) -> T {
+        // the top is being taken out so there is nothing to re-sift on drop
+        this.sift = false;
+        this.heap.top().unwrap()
+    }
+}
+
+impl<T: Ord, const B: usize> Deref for PeekMut<'_, T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.heap.elements[0]
+    }
+}
+
+impl<T: Ord, const B: usize> DerefMut for PeekMut<'_, T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.sift = true;
+        &mut self.heap.elements[0]
+    }
+}
+
+impl<T: Ord, const B: usize> Drop for PeekMut<'_, T, B> {
+    fn drop(&mut self) {
+        if self.sift {
+            self.heap.push_down(0);
+        }
+    }
 }
 
-struct Hole<'a, T: 'a + Ord> {
+/// A slot in `data` whose element has been read out into `elt`, so sifting
+/// can shift other elements over it with one copy each instead of swapping.
+///
+/// While the hole exists `data[pos]` is logically uninitialised. `Drop` writes
+/// `elt` back into it, which also runs when a comparison panics, so `data` is
+/// always left a permutation of its original elements.
+struct Hole<'a, T: 'a> {
     data: &'a mut [T],
     elt: ManuallyDrop<T>,
     pos: usize,
+    moves: usize,
 }
 
-impl<'a, T> Hole<'a, T>
-where
-    T: Ord,
-{
+impl<'a, T> Hole<'a, T> {
     /// Create a new `Hole` at index `pos`.
     ///
     /// Unsafe because pos must be within the data slice.
@@ -29,6 +94,7 @@ where
             data,
             elt: ManuallyDrop::new(elt),
             pos,
+            moves: 0,
         }
     }
 
@@ -67,22 +133,28 @@ where
             ptr::copy_nonoverlapping(index_ptr, hole_ptr, 1);
         }
         self.pos = index;
+        self.moves += 1;
     }
 
     /// Get smallest child index from other elements
     #[inline]
-    fn get_smallest_neighbour_index(
+    fn get_smallest_neighbour_index<F>(
         &self,
         current_index: usize,
         branch_size: usize,
-        mut smallest_child_index: usize,
-    ) -> usize {
-        let guard = std::cmp::min(
+        first_child_index: usize,
+        is_less: &mut F,
+    ) -> usize
+    where
+        F: FnMut(&T, &T) -> bool,
+    {
+        let guard = core::cmp::min(
             get_first_child_index(current_index, branch_size) + branch_size,
             self.data.len(),
         );
-        for child_index in smallest_child_index..guard {
-            if self.data[child_index] > self.data[smallest_child_index] {
+        let mut smallest_child_index = first_child_index;
+        for child_index in first_child_index..guard {
+            if is_less(&self.data[smallest_child_index], &self.data[child_index]) {
                 smallest_child_index = child_index;
             }
         }
@@ -90,10 +162,7 @@ where
     }
 }
 
-impl<T> Drop for Hole<'_, T>
-where
-    T: Ord,
-{
+impl<T> Drop for Hole<'_, T> {
     #[inline]
     fn drop(&mut self) {
         // fill the hole again
@@ -104,54 +173,197 @@ where
     }
 }
 
+/// Where a sift left its element, and how many elements it wrote into a slot
+/// they did not hold before.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Sift {
+    pub(crate) end: usize,
+    // only read by the stats recorder
+    #[cfg_attr(not(feature = "instrumented"), allow(dead_code))]
+    pub(crate) moves: usize,
+}
+
+impl Sift {
+    /// The sift of the element that started at `start` in `hole`, counting the
+    /// write that fills the hole when the element left its slot.
+    fn of<T>(hole: &Hole<'_, T>, start: usize) -> Self {
+        Sift {
+            end: hole.pos(),
+            moves: hole.moves + usize::from(hole.pos() != start),
+        }
+    }
+}
+
+/// Restore the heap property for the subtree rooted at `index`, moving the
+/// element there down past any child that `is_less` ranks above it.
+///
+/// If `is_less` panics, the element is written back into the hole it had
+/// reached, so `data` still holds each element exactly once.
+pub(crate) fn push_down_by<T, F, const B: usize>(
+    data: &mut [T],
+    index: usize,
+    is_less: &mut F,
+) -> Sift
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let array_size = data.len();
+    if index >= array_size {
+        return Sift {
+            end: index,
+            moves: 0,
+        };
+    }
+    let mut current_index = index;
+    let mut smallest_child_index = get_first_child_index(current_index, B);
+    let mut hole = unsafe { Hole::new(data, current_index) };
+    while smallest_child_index < array_size {
+        smallest_child_index =
+            hole.get_smallest_neighbour_index(current_index, B, smallest_child_index, is_less);
+        if is_less(hole.element(), unsafe { hole.get(smallest_child_index) }) {
+            unsafe { hole.move_to(smallest_child_index) };
+            current_index = smallest_child_index;
+            smallest_child_index = get_first_child_index(current_index, B);
+        } else {
+            break;
+        }
+    }
+    Sift::of(&hole, index)
+}
+
+/// Move the element at `index` up towards the root while it ranks above its
+/// parent.
+///
+/// Like [`push_down_by`], a panic in `is_less` leaves every element in `data`
+/// exactly once.
+pub(crate) fn bubble_up_by<T, F, const B: usize>(
+    data: &mut [T],
+    index: usize,
+    is_less: &mut F,
+) -> Sift
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let mut hole = unsafe { Hole::new(data, index) };
+    let mut parent_index;
+    while hole.pos > 0 {
+        parent_index = get_parent_index(hole.pos(), B);
+        if is_less(unsafe { hole.get(parent_index) }, hole.element()) {
+            unsafe { hole.move_to(parent_index) };
+        } else {
+            break;
+        }
+    }
+    Sift::of(&hole, index)
+}
+
+/// Arrange `data` into a `B`-ary max-heap in O(n).
+pub(crate) fn heapify_by<T, F, const B: usize>(data: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    if data.len() < 2 {
+        return;
+    }
+    let parent_index = get_parent_index(data.len() - 1, B);
+    for index in (0..=parent_index).rev() {
+        push_down_by::<T, F, B>(data, index, is_less);
+    }
+}
+
 impl<T: Ord, const B: usize> DHeap<T, B> {
     pub fn new(elements: Vec<T>) -> Self {
-        let mut heap = DHeap { elements };
-        if heap.elements.len() > 0 {
-            heap.heapify()
-        }
+        let mut heap = DHeap {
+            elements,
+            stats: StatsRecorder::new(),
+        };
+        heap.heapify();
         heap
     }
 
-    fn heapify(&mut self) {
-        let element_length = self.elements.len();
-        let parent_index = get_parent_index(element_length - 1, B);
+    pub fn with_capacity(capacity: usize) -> Self {
+        DHeap {
+            elements: Vec::with_capacity(capacity),
+            stats: StatsRecorder::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.elements.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.elements.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.elements.shrink_to_fit();
+    }
+
+    pub fn clear(&mut self) {
+        self.elements.clear();
+    }
+
+    /// Keeps only the elements matching `predicate`, heapifying once
+    /// afterwards if anything was removed.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, predicate: F) {
+        let size = self.elements.len();
+        self.elements.retain(predicate);
+        if self.elements.len() < size {
+            self.heapify();
+        }
+    }
+
+    /// Removes every element, yielding them in heap order rather than sorted
+    /// order. The capacity is kept.
+    pub fn drain(&mut self) -> Drain<'_, T> {
+        self.elements.drain(..)
+    }
 
-        for index in (0..=parent_index).rev() {
+    fn heapify(&mut self) {
+        if self.elements.len() < 2 {
+            return;
+        }
+        for index in (0..=get_parent_index(self.elements.len() - 1, B)).rev() {
             self.push_down(index);
         }
     }
 
     fn push_down(&mut self, index: usize) {
-        let mut current_index = index;
-        let array_size = self.elements.len();
-
-        let mut smallest_child_index = get_first_child_index(current_index, B);
-        let mut hole = unsafe { Hole::new(&mut self.elements, current_index) };
-        while smallest_child_index < array_size {
-            smallest_child_index =
-                hole.get_smallest_neighbour_index(current_index, B, smallest_child_index);
-            if unsafe { hole.get(smallest_child_index) } > hole.element() {
-                unsafe { hole.move_to(smallest_child_index) };
-                current_index = smallest_child_index;
-                smallest_child_index = get_first_child_index(current_index, B);
-            } else {
-                break;
-            }
-        }
+        let sift = push_down_by::<T, _, B>(&mut self.elements, index, &mut self.stats.less_than());
+        self.stats.record_sift(index, sift, B);
     }
 
     fn bubble_up(&mut self, index: usize) {
-        let mut hole = unsafe { Hole::new(&mut self.elements, index) };
-        let mut parent_index;
-        while hole.pos > 0 {
-            parent_index = get_parent_index(hole.pos(), B);
-            if hole.element() > unsafe { hole.get(parent_index) } {
-                unsafe { hole.move_to(parent_index) };
+        let sift = bubble_up_by::<T, _, B>(&mut self.elements, index, &mut self.stats.less_than());
+        self.stats.record_sift(index, sift, B);
+    }
+
+    /// Moves the element at `index` up or down, whichever its new value needs,
+    /// as a single sift.
+    fn restore(&mut self, index: usize) {
+        let sift = {
+            let mut is_less = self.stats.less_than();
+            let sift = bubble_up_by::<T, _, B>(&mut self.elements, index, &mut is_less);
+            if sift.end == index {
+                push_down_by::<T, _, B>(&mut self.elements, index, &mut is_less)
             } else {
-                break;
+                sift
             }
-        }
+        };
+        self.stats.record_sift(index, sift, B);
+    }
+
+    /// Counts of the work done by sifts since creation or the last
+    /// [`reset_stats`](DHeap::reset_stats).
+    #[cfg(feature = "instrumented")]
+    pub fn stats(&self) -> HeapStats {
+        self.stats.stats()
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
     }
 
     pub fn insert(&mut self, element: T) {
@@ -159,6 +371,8 @@ impl<T: Ord, const B: usize> DHeap<T, B> {
         self.bubble_up(self.elements.len() - 1);
     }
 
+    /// Removes and returns the highest element. If the push down panics, the
+    /// element being removed is dropped and the rest stay in the heap.
     pub fn top(&mut self) -> Option<T> {
         self.elements.pop().map(|mut item| {
             if !self.is_empty() {
@@ -171,7 +385,42 @@ impl<T: Ord, const B: usize> DHeap<T, B> {
     }
 
     pub fn peek(&self) -> Option<&T> {
-        self.elements.get(0)
+        self.elements.first()
+    }
+
+    /// Returns a guard giving mutable access to the top element, or `None` if
+    /// the heap is empty. The element is pushed down once when the guard drops.
+    pub fn peek_mut(&mut self) -> Option<PeekMut<'_, T, B>> {
+        if self.is_empty() {
+            None
+        } else {
+            Some(PeekMut {
+                heap: self,
+                sift: false,
+            })
+        }
+    }
+
+    /// Replaces the top element with `element` and returns the old top, doing
+    /// a single push down. Inserts `element` and returns `None` if the heap was
+    /// empty.
+    pub fn replace_top(&mut self, element: T) -> Option<T> {
+        if self.is_empty() {
+            self.insert(element);
+            return None;
+        }
+        let top = replace(&mut self.elements[0], element);
+        self.push_down(0);
+        Some(top)
+    }
+
+    /// Inserts `element` and then removes the top, which is cheaper than doing
+    /// both separately. Returns `element` straight back if nothing outranks it.
+    pub fn push_pop(&mut self, element: T) -> T {
+        match self.elements.first() {
+            Some(top) if *top > element => self.replace_top(element).unwrap(),
+            _ => element,
+        }
     }
 
     pub fn is_empty(&self) -> bool {
@@ -183,20 +432,12 @@ impl<T: Ord, const B: usize> DHeap<T, B> {
     }
 
     pub fn remove(&mut self, element: T) {
-        let size = self.elements.len();
-        let potential_position = self.elements.iter().position(|e| e == &element);
-        if size == 0 && potential_position == None {
+        let Some(position) = self.elements.iter().position(|e| e == &element) else {
             return;
-        }
-        // let else here?
-        if let Some(position) = potential_position {
-            if position == size - 1 {
-                self.elements.remove(position);
-            } else {
-                self.elements.swap(position, size - 1);
-                self.elements.remove(size - 1);
-                self.push_down(position);
-            }
+        };
+        self.elements.swap_remove(position);
+        if position < self.elements.len() {
+            self.restore(position);
         }
     }
 
@@ -205,92 +446,497 @@ impl<T: Ord, const B: usize> DHeap<T, B> {
     }
 
     pub fn update(&mut self, old_element: T, new_element: T) {
-        if self.elements.is_empty() {
+        let Some(position) = self.elements.iter().position(|e| e == &old_element) else {
             return;
+        };
+        self.elements[position] = new_element;
+        self.restore(position);
+    }
+}
+
+impl<T: Ord, const B: usize> Default for DHeap<T, B> {
+    fn default() -> Self {
+        DHeap::with_capacity(0)
+    }
+}
+
+impl<T: Ord + fmt::Debug, const B: usize> Render for DHeap<T, B> {
+    fn to_dot(&self) -> String {
+        render::to_dot("DHeap", &self.elements, B)
+    }
+
+    fn ascii_tree(&self) -> String {
+        render::ascii_tree(&self.elements, B)
+    }
+}
+
+/// An element tagged with the order it was inserted in, so that equal
+/// elements come out first in, first out.
+struct Sequenced<T> {
+    element: T,
+    sequence: u64,
+}
+
+impl<T: Ord> Ord for Sequenced<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.element
+            .cmp(&other.element)
+            // an earlier insert ranks higher
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl<T: Ord> PartialOrd for Sequenced<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> PartialEq for Sequenced<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Sequenced<T> {}
+
+/// A [`DHeap`] that breaks ties between equal elements by insertion order,
+/// making `top` deterministic. Each element carries an extra sequence number.
+pub struct StableDHeap<T: Ord, const B: usize> {
+    heap: DHeap<Sequenced<T>, B>,
+    next_sequence: u64,
+}
+
+impl<T: Ord, const B: usize> StableDHeap<T, B> {
+    /// Creates a heap from `elements`, treating their order in the vector as
+    /// their insertion order.
+    pub fn new(elements: Vec<T>) -> Self {
+        let next_sequence = elements.len() as u64;
+        let elements = elements
+            .into_iter()
+            .zip(0..)
+            .map(|(element, sequence)| Sequenced { element, sequence })
+            .collect();
+        StableDHeap {
+            heap: DHeap::new(elements),
+            next_sequence,
+        }
+    }
+
+    pub fn insert(&mut self, element: T) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.heap.insert(Sequenced { element, sequence });
+    }
+
+    /// Removes the highest element, or the earliest inserted of several equal
+    /// highest elements.
+    pub fn top(&mut self) -> Option<T> {
+        self.heap.top().map(|sequenced| sequenced.element)
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.peek().map(|sequenced| &sequenced.element)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.heap.size()
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        StableDHeap {
+            heap: DHeap::with_capacity(capacity),
+            next_sequence: 0,
         }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.heap.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.heap.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.heap.shrink_to_fit();
+    }
+
+    pub fn clear(&mut self) {
+        self.heap.clear();
+    }
+
+    /// Keeps only the elements matching `predicate`. Equal survivors keep their
+    /// relative insertion order.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut predicate: F) {
+        self.heap.retain(|sequenced| predicate(&sequenced.element));
+    }
+
+    /// Removes every element, yielding them in heap order rather than sorted
+    /// order.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.heap.drain().map(|sequenced| sequenced.element)
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub fn stats(&self) -> HeapStats {
+        self.heap.stats()
+    }
 
-        let must_push_down = new_element > old_element;
-        let position = self
+    #[cfg(feature = "instrumented")]
+    pub fn reset_stats(&mut self) {
+        self.heap.reset_stats();
+    }
+
+    pub fn contains(&self, element: &T) -> bool {
+        self.heap
             .elements
             .iter()
-            .position(|e| e == &old_element)
-            .unwrap();
-        self.elements[position] = new_element;
-        if must_push_down {
-            self.bubble_up(position);
-        } else {
-            self.push_down(position);
-        }
+            .any(|sequenced| &sequenced.element == element)
     }
 }
 
-fn get_parent_index(index: usize, branch_factor: usize) -> usize {
+pub(crate) fn get_parent_index(index: usize, branch_factor: usize) -> usize {
     (index - 1) / branch_factor
 }
 
-fn get_first_child_index(index: usize, branch_factor: usize) -> usize {
+pub(crate) fn get_first_child_index(index: usize, branch_factor: usize) -> usize {
     branch_factor * index + 1
 }
 
+#[cfg(feature = "serde")]
+impl<T: Ord + serde::Serialize, const B: usize> serde::Serialize for DHeap<T, B> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(&self.elements)
+    }
+}
+
+/// A heap serializes as a sequence of its elements in storage order.
+/// Deserializing trusts that order and fails if it isn't a valid heap. Use
+/// [`DHeap::deserialize_reheapify`] to rebuild the order instead.
+#[cfg(feature = "serde")]
+impl<'de, T: Ord + serde::Deserialize<'de>, const B: usize> serde::Deserialize<'de>
+    for DHeap<T, B>
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elements = Vec::<T>::deserialize(deserializer)?;
+        if !super::slice::is_heap::<T, B>(&elements) {
+            return Err(serde::de::Error::custom("elements are not in heap order"));
+        }
+        Ok(DHeap {
+            elements,
+            stats: StatsRecorder::new(),
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: Ord, const B: usize> DHeap<T, B> {
+    /// Deserializes the elements in any order and heapifies them, for use with
+    /// `#[serde(deserialize_with = "DHeap::deserialize_reheapify")]`.
+    pub fn deserialize_reheapify<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        T: serde::Deserialize<'de>,
+    {
+        use serde::Deserialize;
+        Ok(DHeap::new(Vec::<T>::deserialize(deserializer)?))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::heaps::test_support::{
+        self, d_heap_variant_tests, panics_on_comparison, priority_queue_tests, TestHeap, Tracked,
+    };
+
+    impl TestHeap for DHeap<u64, 3> {
+        fn insert(&mut self, element: u64) {
+            DHeap::insert(self, element);
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            DHeap::top(self)
+        }
+
+        fn peek(&self) -> Option<u64> {
+            DHeap::peek(self).copied()
+        }
+
+        fn size(&self) -> usize {
+            DHeap::size(self)
+        }
+    }
 
     fn create_heap() -> DHeap<u64, 3> {
-        DHeap::new(vec![9, 10, 9, 8, 7, 5, 3, 8])
+        test_support::create_heap()
     }
 
+    priority_queue_tests!(DHeap<u64, 3>);
+    d_heap_variant_tests!(DHeap::<u64, 2>::with_capacity);
+
     #[test]
-    fn it_should_have_expected_order() {
-        let expected_ordering = vec![10, 9, 9, 8, 8, 7, 5, 3];
+    fn it_should_be_able_to_remove_elements() {
+        let expected_ordering = vec![10, 9, 8, 7, 5, 3];
         let mut heap = create_heap();
+        heap.remove(9);
+        heap.remove(8);
         for number in expected_ordering {
             let actual = heap.top().unwrap();
             assert_eq!(actual, number);
         }
+        assert_eq!(None, heap.peek());
         assert!(heap.is_empty());
     }
 
     #[test]
-    fn it_should_be_able_to_insert_elements() {
-        let expected_ordering = vec![13, 11, 10, 9, 9, 8, 8, 7, 5, 3];
+    fn it_should_be_able_to_update_elements() {
+        let expected_ordering = vec![14, 10, 9, 9, 8, 7, 5, 3];
         let mut heap = create_heap();
-        heap.insert(13);
-        heap.insert(11);
-        assert_eq!(Some(&13), heap.peek());
-        assert_eq!(10, heap.size());
+        heap.update(8, 14);
+        assert_eq!(Some(&14), heap.peek());
         for number in expected_ordering {
             let actual = heap.top().unwrap();
             assert_eq!(actual, number);
         }
-        assert_eq!(None, heap.peek());
         assert!(heap.is_empty());
     }
 
     #[test]
-    fn it_should_be_able_to_remove_elements() {
-        let expected_ordering = vec![10, 9, 8, 7, 5, 3];
+    fn it_should_restore_order_after_peek_mut() {
+        let expected_ordering = vec![9, 9, 8, 8, 7, 5, 3, 1];
         let mut heap = create_heap();
-        heap.remove(9);
-        heap.remove(8);
+        *heap.peek_mut().unwrap() = 1;
         for number in expected_ordering {
             let actual = heap.top().unwrap();
             assert_eq!(actual, number);
         }
-        assert_eq!(None, heap.peek());
-        assert!(heap.is_empty());
+        assert!(heap.peek_mut().is_none());
     }
 
     #[test]
-    fn it_should_be_able_to_update_elements() {
-        let expected_ordering = vec![14, 10, 9, 9, 8, 7, 5, 3];
+    fn it_should_pop_through_peek_mut() {
         let mut heap = create_heap();
-        heap.update(8, 14);
-        assert_eq!(Some(&14), heap.peek());
-        for number in expected_ordering {
-            let actual = heap.top().unwrap();
-            assert_eq!(actual, number);
+        let top = PeekMut::pop(heap.peek_mut().unwrap());
+        assert_eq!(10, top);
+        assert_eq!(Some(&9), heap.peek());
+        assert_eq!(7, heap.size());
+    }
+
+    #[test]
+    fn it_should_replace_and_push_pop_the_top() {
+        let mut heap = create_heap();
+        assert_eq!(Some(10), heap.replace_top(4));
+        assert_eq!(Some(&9), heap.peek());
+        assert_eq!(11, heap.push_pop(11));
+        assert_eq!(9, heap.push_pop(2));
+        assert_eq!(8, heap.size());
+
+        let mut empty: DHeap<u64, 3> = DHeap::new(vec![]);
+        assert_eq!(None, empty.replace_top(4));
+        assert_eq!(Some(&4), empty.peek());
+    }
+
+    /// Ordered by priority alone so jobs with the same priority compare equal.
+    #[derive(Debug)]
+    struct Job {
+        priority: u8,
+        id: usize,
+    }
+
+    impl Ord for Job {
+        fn cmp(&self, other: &Self) -> Ordering {
+            self.priority.cmp(&other.priority)
+        }
+    }
+
+    impl PartialOrd for Job {
+        fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl PartialEq for Job {
+        fn eq(&self, other: &Self) -> bool {
+            self.priority == other.priority
+        }
+    }
+
+    impl Eq for Job {}
+
+    #[test]
+    fn it_should_keep_fifo_order_among_equal_elements_when_stable() {
+        let jobs = (0..5_000).map(|id| Job {
+            priority: (id % 3) as u8,
+            id,
+        });
+        let mut heap: StableDHeap<Job, 3> = StableDHeap::new(jobs.clone().take(2_500).collect());
+        for job in jobs.skip(2_500) {
+            heap.insert(job);
+        }
+        assert_eq!(5_000, heap.size());
+        assert!(heap.contains(&Job { priority: 2, id: 0 }));
+        let mut previous: Option<Job> = None;
+        while let Some(job) = heap.top() {
+            if let Some(previous) = &previous {
+                assert!(previous.priority > job.priority || previous.id < job.id);
+            }
+            previous = Some(job);
         }
         assert!(heap.is_empty());
     }
+
+    #[test]
+    fn it_should_ignore_missing_elements_and_bubble_up_replacements() {
+        let mut heap: DHeap<u64, 2> = DHeap::new(vec![]);
+        heap.remove(1);
+        heap.update(1, 2);
+        assert_eq!(None, heap.top());
+
+        // removing 6 moves the last element, 15, under 13 where it has to
+        // bubble up
+        let mut heap: DHeap<u64, 2> = DHeap::new(vec![19, 13, 16, 6, 9, 4, 15]);
+        heap.remove(6);
+        heap.update(42, 3);
+        let popped: Vec<u64> = core::iter::from_fn(|| heap.top()).collect();
+        assert_eq!(vec![19, 16, 15, 13, 9, 4], popped);
+    }
+
+    /// The variants sift alike, so they should agree on the work they record.
+    #[cfg(feature = "instrumented")]
+    #[test]
+    fn it_should_record_the_same_stats_for_every_variant() {
+        use crate::heaps::{d_heap_arc, d_way_heap_clone};
+
+        macro_rules! run {
+            ($heap:expr) => {{
+                let mut heap = $heap;
+                heap.update(4, 30);
+                heap.update(19, 1);
+                heap.update(9, 9);
+                heap.remove(16);
+                heap.insert(12);
+                while heap.top().is_some() {}
+                heap.stats()
+            }};
+        }
+        let elements = vec![19, 13, 16, 6, 9, 4, 15];
+        let stats = run!(DHeap::<u64, 2>::new(elements.clone()));
+        assert_eq!(
+            stats,
+            run!(d_heap_arc::DHeap::<u64, 2>::new(elements.clone()))
+        );
+        assert_eq!(stats, run!(d_way_heap_clone::DHeap::new(elements, Some(2))));
+        // three for the heapify, one per update, remove and insert, and one for
+        // every top that leaves elements behind
+        assert_eq!(3 + 3 + 1 + 1 + 6, stats.sifts);
+    }
+
+    #[test]
+    fn it_should_retain_and_drain_when_stable() {
+        let mut heap = StableDHeap::<Job, 2>::with_capacity(10);
+        for id in 0..10 {
+            heap.insert(Job {
+                priority: (id % 2) as u8,
+                id,
+            });
+        }
+        heap.retain(|job| job.id % 3 != 0);
+        let ids: Vec<usize> = core::iter::from_fn(|| heap.top())
+            .map(|job| job.id)
+            .collect();
+        assert_eq!(vec![1, 5, 7, 2, 4, 8], ids);
+        heap.insert(Job { priority: 0, id: 0 });
+        assert_eq!(1, heap.drain().count());
+        assert!(heap.is_empty());
+    }
+
+    fn create_tracked_heap(drops: &Rc<Cell<usize>>) -> DHeap<Tracked, 3> {
+        DHeap::new((0..20).map(|n| Tracked::new((n * 7) % 20, drops)).collect())
+    }
+
+    fn sorted_values(heap: &DHeap<Tracked, 3>) -> Vec<u64> {
+        let mut values: Vec<u64> = heap.elements.iter().map(|e| e.value).collect();
+        values.sort_unstable();
+        values
+    }
+
+    #[test]
+    fn it_should_not_double_drop_or_leak_when_top_panics() {
+        for nth in 1.. {
+            let drops = Rc::new(Cell::new(0));
+            let mut heap = create_tracked_heap(&drops);
+            if !panics_on_comparison(nth, || heap.top()) {
+                assert!(nth > 3);
+                break;
+            }
+            // only the popped top was dropped while unwinding
+            assert_eq!(1, drops.get());
+            assert_eq!((0..19).collect::<Vec<_>>(), sorted_values(&heap));
+            drop(heap);
+            assert_eq!(20, drops.get());
+        }
+    }
+
+    #[test]
+    fn it_should_not_double_drop_or_leak_when_bubble_up_panics() {
+        for nth in 1.. {
+            let drops = Rc::new(Cell::new(0));
+            let mut heap = create_tracked_heap(&drops);
+            let inserted = Tracked::new(20, &drops);
+            if !panics_on_comparison(nth, || heap.insert(inserted)) {
+                assert!(nth > 2);
+                break;
+            }
+            assert_eq!(0, drops.get());
+            assert_eq!((0..21).collect::<Vec<_>>(), sorted_values(&heap));
+            assert_eq!(21, heap.size());
+            drop(heap);
+            assert_eq!(21, drops.get());
+        }
+    }
+
+    #[test]
+    fn it_should_not_double_drop_or_leak_when_push_down_panics() {
+        for nth in 1.. {
+            let drops = Rc::new(Cell::new(0));
+            let mut heap = create_tracked_heap(&drops);
+            let replaced = Tracked::new(0, &drops);
+            if !panics_on_comparison(nth, || heap.replace_top(replaced)) {
+                assert!(nth > 3);
+                break;
+            }
+            // the old top is handed back only after the push down
+            assert_eq!(1, drops.get());
+            let mut expected: Vec<u64> = (0..19).collect();
+            expected.insert(0, 0);
+            assert_eq!(expected, sorted_values(&heap));
+            drop(heap);
+            assert_eq!(21, drops.get());
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_should_round_trip_through_serde() {
+        let heap = create_heap();
+        let json = serde_json::to_string(&heap).unwrap();
+        let mut restored: DHeap<u64, 3> = serde_json::from_str(&json).unwrap();
+        assert_eq!(Some(10), restored.top());
+        assert_eq!(7, restored.size());
+
+        let unordered = "[1, 2, 3]";
+        assert!(serde_json::from_str::<DHeap<u64, 3>>(unordered).is_err());
+        let mut deserializer = serde_json::Deserializer::from_str(unordered);
+        let mut reheapified: DHeap<u64, 3> =
+            DHeap::deserialize_reheapify(&mut deserializer).unwrap();
+        assert_eq!(Some(3), reheapified.top());
+    }
 }
@@ -1,31 +1,61 @@
 use core::hash::Hash;
-use std::collections::HashMap;
+use std::collections::{hash_map::Entry, HashMap};
 use std::sync::Arc;
 
+use super::d_heap::Sift;
+use super::render::{self, Render};
+#[cfg(feature = "instrumented")]
+use super::stats::HeapStats;
+use super::stats::StatsRecorder;
+
 type PositionHashMap<T> = HashMap<Arc<T>, usize>;
 
+/// A `B`-ary max-heap of distinct elements with a position map, so `remove`,
+/// `update` and `contains` find an element without a scan.
+///
+/// The map holds one position per element, so the heap never holds two equal
+/// elements: [`insert`](DHeap::insert) and [`update`](DHeap::update) refuse
+/// them and [`new`](DHeap::new) keeps the first of each.
+///
+/// # Serde
+///
+/// Serializes like [`d_heap::DHeap`](crate::heaps::d_heap::DHeap), as a
+/// sequence of its elements in storage order. Deserializing fails if they are
+/// not in heap order or if two of them are equal.
 #[derive(Debug)]
 pub struct DHeap<T: Eq + Hash + PartialOrd + std::fmt::Debug, const B: usize> {
     elements: Vec<Arc<T>>,
     element_positions: PositionHashMap<T>,
+    stats: StatsRecorder,
 }
 
 impl<T, const B: usize> DHeap<T, B>
 where
     T: Eq + Hash + PartialOrd + std::fmt::Debug,
 {
+    /// Builds a heap from `elements`, dropping any element equal to an earlier
+    /// one.
     pub fn new(elements: Vec<T>) -> Self {
-        let element_positions: PositionHashMap<T> = HashMap::new();
-        let mut d_heap = DHeap {
-            elements: elements.into_iter().map(|e| Arc::new(e)).collect(),
-            element_positions,
-        };
+        let mut d_heap = DHeap::with_capacity(elements.len());
+        for element in elements {
+            let element = Arc::new(element);
+            if let Entry::Vacant(entry) = d_heap.element_positions.entry(element.clone()) {
+                entry.insert(d_heap.elements.len());
+                d_heap.elements.push(element);
+            }
+        }
         d_heap.heapify();
         d_heap
     }
 
     fn heapify(&mut self) {
         let element_length = self.elements.len();
+        if element_length < 2 {
+            if let Some(element) = self.elements.first() {
+                self.element_positions.insert(element.clone(), 0);
+            }
+            return;
+        }
         let parent_index = self.get_parent_index(element_length - 1);
         let starting_index = parent_index + 1;
         for index in starting_index..element_length {
@@ -43,15 +73,24 @@ where
     }
 
     fn bubble_up(&mut self, index: usize) {
+        let sift = self.sift_up(index);
+        self.stats.record_sift(index, sift, B);
+    }
+
+    fn sift_up(&mut self, index: usize) -> Sift {
         let current = self.elements[index].clone();
         let mut bubble_up_index = index;
         let mut parent_index;
+        let mut moves = 0;
         while bubble_up_index > 0 {
             parent_index = self.get_parent_index(bubble_up_index);
             let parent = self.elements[parent_index].clone();
+            self.stats.record_comparison();
             if current > parent {
-                self.element_positions.insert(parent.clone(), parent_index);
+                self.element_positions
+                    .insert(parent.clone(), bubble_up_index);
                 self.elements[bubble_up_index] = parent;
+                moves += 1;
                 bubble_up_index = parent_index
             } else {
                 break;
@@ -60,31 +99,45 @@ where
         self.element_positions
             .insert(current.clone(), bubble_up_index);
         self.elements[bubble_up_index] = current;
+        Sift {
+            end: bubble_up_index,
+            moves: moves + usize::from(bubble_up_index != index),
+        }
     }
 
     fn get_first_child_index(&self, index: usize) -> usize {
-        return (B * index) + 1;
+        (B * index) + 1
     }
 
     fn push_down(&mut self, index: usize) {
+        let sift = self.sift_down(index);
+        self.stats.record_sift(index, sift, B);
+    }
+
+    fn sift_down(&mut self, index: usize) -> Sift {
         let mut push_down_index = index;
+        let mut moves = 0;
         let size = self.elements.len();
         let current = self.elements[push_down_index].clone();
-        let mut smallest_children_index = self.get_first_child_index(push_down_index);
-        while smallest_children_index < size {
-            let guard = std::cmp::min(self.get_first_child_index(push_down_index) + B, size);
-            for children_index in smallest_children_index..guard {
+        let mut first_children_index = self.get_first_child_index(push_down_index);
+        while first_children_index < size {
+            let guard = std::cmp::min(first_children_index + B, size);
+            let mut smallest_children_index = first_children_index;
+            for children_index in first_children_index..guard {
+                self.stats.record_comparison();
                 if self.elements[children_index] > self.elements[smallest_children_index] {
                     smallest_children_index = children_index;
                 }
             }
             let child = self.elements[smallest_children_index].clone();
+            self.stats.record_comparison();
             if child > current {
                 self.element_positions
                     .insert(child.clone(), push_down_index);
                 self.elements[push_down_index] = child;
+                moves += 1;
                 push_down_index = smallest_children_index;
-                smallest_children_index = self.get_first_child_index(push_down_index)
+                first_children_index = self.get_first_child_index(push_down_index)
             } else {
                 break;
             }
@@ -93,44 +146,57 @@ where
         self.element_positions
             .insert(current.clone(), push_down_index);
         self.elements[push_down_index] = current;
+        Sift {
+            end: push_down_index,
+            moves: moves + usize::from(push_down_index != index),
+        }
+    }
+
+    /// Moves the element at `index` up or down, whichever its new value needs,
+    /// as a single sift.
+    fn restore(&mut self, index: usize) {
+        let mut sift = self.sift_up(index);
+        if sift.end == index {
+            sift = self.sift_down(index);
+        }
+        self.stats.record_sift(index, sift, B);
     }
 
-    pub fn insert(&mut self, element: T) {
+    /// Inserts `element`, returning `false` and leaving the heap unchanged if
+    /// an equal element is already present.
+    pub fn insert(&mut self, element: T) -> bool {
+        if self.element_positions.contains_key(&element) {
+            return false;
+        }
         let item = Arc::new(element);
         self.elements.push(item);
         self.bubble_up(self.elements.len() - 1);
+        true
     }
 
     pub fn top(&mut self) -> Option<T> {
-        let top_element = self.elements.pop();
+        let top_element = self.elements.pop()?;
         // convert to map...
         if self.elements.is_empty() {
-            let el = top_element.unwrap();
             self.element_positions
-                .remove(&el)
+                .remove(&top_element)
                 .expect("mismatched keys A");
-            Some(
-                Arc::try_unwrap(el)
-                    .ok()
-                    .expect("Too many owners for element of top"),
-            )
+            Some(Arc::try_unwrap(top_element).expect("Too many owners for element of top"))
         } else {
             let first_element = self.elements[0].clone();
-            self.elements[0] = top_element.unwrap();
+            self.elements[0] = top_element;
             self.push_down(0);
             self.element_positions
                 .remove(&first_element)
                 .expect("mismatched keys B");
             Some(
-                Arc::try_unwrap(first_element)
-                    .ok()
-                    .expect("Too many owners for else branch of element"),
+                Arc::try_unwrap(first_element).expect("Too many owners for else branch of element"),
             )
         }
     }
 
-    fn peek(&self) -> Option<&T> {
-        self.elements.get(0).map(|arc| &**arc)
+    pub fn peek(&self) -> Option<&T> {
+        self.elements.first().map(|arc| &**arc)
     }
 
     pub fn contains(&self, element: &T) -> bool {
@@ -142,52 +208,188 @@ where
     }
 
     pub fn remove(&mut self, element: T) {
-        let size = self.elements.len();
-        let potential_position = self.element_positions.get(&element);
-        if size == 0 && potential_position == None {
+        let Some(position) = self.element_positions.remove(&element) else {
             return;
-        }
-        // let else here?
-        let position = *potential_position.unwrap();
-        if position == size - 1 {
-            self.elements.remove(position);
-            self.element_positions.remove(&element);
-        } else {
-            let element_to_replace_with = self.elements[size - 1].clone();
-            self.elements[position] = element_to_replace_with;
-            self.elements.remove(size - 1);
-            self.element_positions.remove(&element);
-            self.push_down(position);
+        };
+        let last = self.elements.pop().unwrap();
+        if position < self.elements.len() {
+            self.elements[position] = last;
+            self.restore(position);
         }
     }
 
-    pub fn update(&mut self, old_element: T, new_element: T) {
-        if self.elements.is_empty() || !self.element_positions.contains_key(&old_element) {
-            return;
+    /// Replaces `old_element` with `new_element`. Returns `false` and leaves
+    /// the heap unchanged if `old_element` is missing or another element
+    /// equals `new_element`.
+    pub fn update(&mut self, old_element: T, new_element: T) -> bool {
+        if new_element != old_element && self.element_positions.contains_key(&new_element) {
+            return false;
         }
-        let position = *self.element_positions.get(&old_element).unwrap();
-        let must_push_down = new_element > old_element;
+        let Some(position) = self.element_positions.remove(&old_element) else {
+            return false;
+        };
         self.elements[position] = Arc::new(new_element);
-        if must_push_down {
-            self.bubble_up(position);
-        } else {
-            self.push_down(position);
-        }
+        self.restore(position);
+        true
     }
 
     pub fn size(&self) -> usize {
         self.elements.len()
     }
+
+    /// Counts of the work done by sifts since creation or the last
+    /// [`reset_stats`](DHeap::reset_stats).
+    #[cfg(feature = "instrumented")]
+    pub fn stats(&self) -> HeapStats {
+        self.stats.stats()
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        DHeap {
+            elements: Vec::with_capacity(capacity),
+            element_positions: HashMap::with_capacity(capacity),
+            stats: StatsRecorder::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.elements.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.elements.reserve(additional);
+        self.element_positions.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.elements.shrink_to_fit();
+        self.element_positions.shrink_to_fit();
+    }
+
+    pub fn clear(&mut self) {
+        self.elements.clear();
+        self.element_positions.clear();
+    }
+
+    /// Keeps only the elements matching `predicate`, rebuilding the heap and
+    /// its position map once afterwards if anything was removed.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut predicate: F) {
+        let size = self.elements.len();
+        self.elements.retain(|element| predicate(element));
+        if self.elements.len() < size {
+            self.element_positions.clear();
+            self.heapify();
+        }
+    }
+
+    /// Removes every element, yielding them in heap order rather than sorted
+    /// order.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        // drop the map's references first so each element has a single owner
+        self.element_positions.clear();
+        self.elements
+            .drain(..)
+            .map(|element| Arc::try_unwrap(element).expect("Too many owners for drained element"))
+    }
+}
+
+impl<T, const B: usize> Render for DHeap<T, B>
+where
+    T: Eq + Hash + PartialOrd + std::fmt::Debug,
+{
+    fn to_dot(&self) -> String {
+        render::to_dot("DHeap", &self.elements, B)
+    }
+
+    fn ascii_tree(&self) -> String {
+        render::ascii_tree(&self.elements, B)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, const B: usize> serde::Serialize for DHeap<T, B>
+where
+    T: Eq + Hash + PartialOrd + std::fmt::Debug + serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.elements.iter().map(|element| &**element))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T, const B: usize> serde::Deserialize<'de> for DHeap<T, B>
+where
+    T: Eq + Hash + PartialOrd + std::fmt::Debug + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let elements: Vec<Arc<T>> = Vec::<T>::deserialize(deserializer)?
+            .into_iter()
+            .map(Arc::new)
+            .collect();
+        let mut d_heap = DHeap {
+            elements,
+            element_positions: HashMap::new(),
+            stats: StatsRecorder::new(),
+        };
+        let in_order = (1..d_heap.elements.len())
+            .all(|index| d_heap.elements[index] <= d_heap.elements[d_heap.get_parent_index(index)]);
+        if !in_order {
+            return Err(serde::de::Error::custom("elements are not in heap order"));
+        }
+        for (index, element) in d_heap.elements.iter().enumerate() {
+            if d_heap
+                .element_positions
+                .insert(element.clone(), index)
+                .is_some()
+            {
+                return Err(serde::de::Error::custom("elements are not distinct"));
+            }
+        }
+        Ok(d_heap)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T, const B: usize> DHeap<T, B>
+where
+    T: Eq + Hash + PartialOrd + std::fmt::Debug,
+{
+    /// Deserializes a sequence of distinct elements in any order and heapifies
+    /// them, for use with
+    /// `#[serde(deserialize_with = "DHeap::deserialize_reheapify")]`.
+    pub fn deserialize_reheapify<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        T: serde::Deserialize<'de>,
+    {
+        use serde::Deserialize;
+        let elements = Vec::<T>::deserialize(deserializer)?;
+        let size = elements.len();
+        let d_heap = DHeap::new(elements);
+        if d_heap.element_positions.len() < size {
+            return Err(serde::de::Error::custom("elements are not distinct"));
+        }
+        Ok(d_heap)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::heaps::test_support::d_heap_variant_tests;
+
     fn create_heap() -> DHeap<u64, 3> {
         DHeap::new(vec![9, 10, 3, 5, 7, 8, 11])
     }
 
+    d_heap_variant_tests!(DHeap::<u64, 2>::with_capacity);
+
     #[test]
     fn it_should_have_expected_order() {
         let expected_ordering = vec![11, 10, 9, 8, 7, 5, 3];
@@ -241,4 +443,60 @@ mod tests {
         }
         assert!(heap.is_empty());
     }
+
+    #[test]
+    fn it_should_keep_positions_in_sync_with_elements() {
+        let mut heap: DHeap<u64, 2> = DHeap::new(vec![]);
+        assert_eq!(None, heap.top());
+        heap.remove(1);
+        heap.insert(49);
+        heap.insert(53);
+        heap.update(49, 10);
+        assert!(!heap.contains(&49));
+        assert_eq!(Some(&53), heap.peek());
+        heap.remove(53);
+        assert_eq!(Some(10), heap.top());
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_refuse_equal_elements() {
+        let mut heap: DHeap<u64, 2> = DHeap::new(vec![1, 4, 1]);
+        assert_eq!(2, heap.size());
+        assert!(!heap.insert(4));
+        assert!(heap.insert(7));
+        assert!(!heap.update(1, 7));
+        assert!(!heap.update(2, 3));
+        assert!(heap.update(4, 4));
+        assert!(heap.update(1, 5));
+        let popped: Vec<u64> = (0..heap.size()).map(|_| heap.top().unwrap()).collect();
+        assert_eq!(vec![7, 5, 4], popped);
+        assert_eq!(None, heap.top());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_should_round_trip_through_serde() {
+        let heap = create_heap();
+        let json = serde_json::to_string(&heap).unwrap();
+        let mut restored: DHeap<u64, 3> = serde_json::from_str(&json).unwrap();
+        assert!(restored.contains(&3));
+        restored.update(3, 12);
+        assert_eq!(Some(12), restored.top());
+        assert_eq!(Some(11), restored.top());
+
+        let unordered = "[1, 2, 3]";
+        assert!(serde_json::from_str::<DHeap<u64, 3>>(unordered).is_err());
+        let mut deserializer = serde_json::Deserializer::from_str(unordered);
+        let mut reheapified: DHeap<u64, 3> =
+            DHeap::deserialize_reheapify(&mut deserializer).unwrap();
+        assert!(reheapified.contains(&1));
+        assert_eq!(Some(3), reheapified.top());
+        let empty: DHeap<u64, 3> = serde_json::from_str("[]").unwrap();
+        assert!(empty.is_empty());
+
+        assert!(serde_json::from_str::<DHeap<u64, 3>>("[1,1]").is_err());
+        let mut deserializer = serde_json::Deserializer::from_str("[1,2,1]");
+        assert!(DHeap::<u64, 3>::deserialize_reheapify(&mut deserializer).is_err());
+    }
 }
@@ -1,11 +1,35 @@
-use std::{collections::HashMap, hash::Hash};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    hash::Hash,
+};
+
+use super::d_heap::Sift;
+use super::render::{self, Render};
+#[cfg(feature = "instrumented")]
+use super::stats::HeapStats;
+use super::stats::StatsRecorder;
 
 type PositionHashMap<T> = HashMap<T, usize>;
 
+/// A max-heap of distinct elements whose branch factor is picked at runtime,
+/// with a position map so `remove`, `update` and `contains` find an element
+/// without a scan.
+///
+/// The map holds one position per element, so the heap never holds two equal
+/// elements: [`insert`](DHeap::insert) and [`update`](DHeap::update) refuse
+/// them and [`new`](DHeap::new) keeps the first of each.
+///
+/// # Serde
+///
+/// Serializes as a struct holding the branch factor and the elements in
+/// storage order, e.g. `{"branch_factor": 2, "elements": [3, 2, 1]}`.
+/// Deserializing reads the same struct back and fails if the branch factor is
+/// zero, the elements are not in heap order or two of them are equal.
 #[derive(Debug)]
 pub struct DHeap<T: Eq + Hash + Clone + PartialOrd + std::fmt::Debug> {
     elements: Vec<T>,
     element_positions: PositionHashMap<T>,
+    stats: StatsRecorder,
     branch_factor: u64,
 }
 
@@ -14,20 +38,28 @@ impl<T> DHeap<T>
 where
     T: Eq + Hash + Clone + PartialOrd + std::fmt::Debug,
 {
+    /// Builds a heap from `elements`, dropping any element equal to an earlier
+    /// one.
     pub fn new(elements: Vec<T>, branching_factor: Option<u64>) -> Self {
-        let branch_factor = branching_factor.unwrap_or(2);
-        let element_positions: PositionHashMap<T> = HashMap::new();
-        let mut d_heap = DHeap {
-            elements,
-            element_positions,
-            branch_factor,
-        };
+        let mut d_heap = DHeap::with_capacity(elements.len(), branching_factor);
+        for element in elements {
+            if let Entry::Vacant(entry) = d_heap.element_positions.entry(element.clone()) {
+                entry.insert(d_heap.elements.len());
+                d_heap.elements.push(element);
+            }
+        }
         d_heap.heapify();
         d_heap
     }
 
     fn heapify(&mut self) {
         let element_length = self.elements.len();
+        if element_length < 2 {
+            if let Some(element) = self.elements.first() {
+                self.element_positions.insert(element.clone(), 0);
+            }
+            return;
+        }
         let parent_index = self.get_parent_index(element_length - 1);
         let starting_index = parent_index + 1;
         for index in starting_index..element_length {
@@ -47,15 +79,25 @@ where
     // does 'remove' instead of clone break the algorithm I wonder?
 
     fn bubble_up(&mut self, index: usize) {
+        let sift = self.sift_up(index);
+        self.stats
+            .record_sift(index, sift, self.branch_factor as usize);
+    }
+
+    fn sift_up(&mut self, index: usize) -> Sift {
         let current = self.elements[index].clone();
         let mut bubble_up_index = index;
         let mut parent_index;
+        let mut moves = 0;
         while bubble_up_index > 0 {
             parent_index = self.get_parent_index(bubble_up_index);
             let parent = self.elements[parent_index].clone();
+            self.stats.record_comparison();
             if current > parent {
-                self.element_positions.insert(parent.clone(), parent_index);
+                self.element_positions
+                    .insert(parent.clone(), bubble_up_index);
                 self.elements[bubble_up_index] = parent;
+                moves += 1;
                 bubble_up_index = parent_index
             } else {
                 break;
@@ -64,35 +106,47 @@ where
         self.element_positions
             .insert(current.clone(), bubble_up_index);
         self.elements[bubble_up_index] = current;
+        Sift {
+            end: bubble_up_index,
+            moves: moves + usize::from(bubble_up_index != index),
+        }
     }
 
     fn get_first_child_index(&self, index: usize) -> usize {
-        return (self.branch_factor as usize * index) + 1;
+        (self.branch_factor as usize * index) + 1
     }
 
     fn push_down(&mut self, index: usize) {
+        let sift = self.sift_down(index);
+        self.stats
+            .record_sift(index, sift, self.branch_factor as usize);
+    }
+
+    fn sift_down(&mut self, index: usize) -> Sift {
         let mut push_down_index = index;
+        let mut moves = 0;
         let size = self.elements.len();
         let current = self.elements[push_down_index].clone();
-        let mut smallest_children_index = self.get_first_child_index(push_down_index);
-        while smallest_children_index < size {
-            let guard = std::cmp::min(
-                self.get_first_child_index(push_down_index) + self.branch_factor as usize,
-                size,
-            );
-            for children_index in smallest_children_index..guard {
+        let mut first_children_index = self.get_first_child_index(push_down_index);
+        while first_children_index < size {
+            let guard = std::cmp::min(first_children_index + self.branch_factor as usize, size);
+            let mut smallest_children_index = first_children_index;
+            for children_index in first_children_index..guard {
+                self.stats.record_comparison();
                 if self.elements[children_index] > self.elements[smallest_children_index] {
                     smallest_children_index = children_index;
                 }
             }
             let child = self.elements[smallest_children_index].clone();
             // check priorities
+            self.stats.record_comparison();
             if child > current {
                 self.element_positions
                     .insert(child.clone(), push_down_index);
                 self.elements[push_down_index] = child;
+                moves += 1;
                 push_down_index = smallest_children_index;
-                smallest_children_index = self.get_first_child_index(push_down_index)
+                first_children_index = self.get_first_child_index(push_down_index)
             } else {
                 break;
             }
@@ -101,27 +155,50 @@ where
         self.element_positions
             .insert(current.clone(), push_down_index);
         self.elements[push_down_index] = current;
+        Sift {
+            end: push_down_index,
+            moves: moves + usize::from(push_down_index != index),
+        }
     }
 
-    pub fn insert(&mut self, element: T) {
+    /// Moves the element at `index` up or down, whichever its new value needs,
+    /// as a single sift.
+    fn restore(&mut self, index: usize) {
+        let mut sift = self.sift_up(index);
+        if sift.end == index {
+            sift = self.sift_down(index);
+        }
+        self.stats
+            .record_sift(index, sift, self.branch_factor as usize);
+    }
+
+    /// Inserts `element`, returning `false` and leaving the heap unchanged if
+    /// an equal element is already present.
+    pub fn insert(&mut self, element: T) -> bool {
+        if self.element_positions.contains_key(&element) {
+            return false;
+        }
         self.elements.push(element);
         self.bubble_up(self.elements.len() - 1);
+        true
     }
 
     pub fn top(&mut self) -> Option<T> {
-        let top_element = self.elements.pop();
+        let top_element = self.elements.pop()?;
         if self.elements.is_empty() {
-            return top_element;
+            self.element_positions.remove(&top_element);
+            Some(top_element)
         } else {
             let first_element = self.elements[0].clone();
-            self.elements[0] = top_element.unwrap();
+            self.element_positions.remove(&first_element);
+            self.elements[0] = top_element;
             self.push_down(0);
             Some(first_element)
         }
     }
 
     pub fn peek(&self) -> Option<&T> {
-        self.elements.get(0)
+        self.elements.first()
     }
 
     pub fn contains(&self, element: &T) -> bool {
@@ -133,52 +210,208 @@ where
     }
 
     pub fn remove(&mut self, element: T) {
-        let size = self.elements.len();
-        let potential_position = self.element_positions.get(&element);
-        if size == 0 && potential_position == None {
+        let Some(position) = self.element_positions.remove(&element) else {
             return;
-        }
-        // let else here?
-        let position = *potential_position.unwrap();
-        if position == size - 1 {
-            self.elements.remove(position);
-            self.element_positions.remove(&element);
-        } else {
-            let element_to_replace_with = self.elements[size - 1].clone();
-            self.elements[position] = element_to_replace_with;
-            self.elements.remove(size - 1);
-            self.element_positions.remove(&element);
-            self.push_down(position);
+        };
+        let last = self.elements.pop().unwrap();
+        if position < self.elements.len() {
+            self.elements[position] = last;
+            self.restore(position);
         }
     }
 
-    pub fn update(&mut self, old_element: T, new_element: T) {
-        if self.elements.is_empty() || !self.element_positions.contains_key(&old_element) {
-            return;
+    /// Replaces `old_element` with `new_element`. Returns `false` and leaves
+    /// the heap unchanged if `old_element` is missing or another element
+    /// equals `new_element`.
+    pub fn update(&mut self, old_element: T, new_element: T) -> bool {
+        if new_element != old_element && self.element_positions.contains_key(&new_element) {
+            return false;
         }
-        let position = *self.element_positions.get(&old_element).unwrap();
-        let must_push_down = new_element > old_element;
+        let Some(position) = self.element_positions.remove(&old_element) else {
+            return false;
+        };
         self.elements[position] = new_element;
-        if must_push_down {
-            self.bubble_up(position);
-        } else {
-            self.push_down(position);
-        }
+        self.restore(position);
+        true
     }
 
     pub fn size(&self) -> usize {
         self.elements.len()
     }
+
+    /// Counts of the work done by sifts since creation or the last
+    /// [`reset_stats`](DHeap::reset_stats).
+    #[cfg(feature = "instrumented")]
+    pub fn stats(&self) -> HeapStats {
+        self.stats.stats()
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub fn reset_stats(&mut self) {
+        self.stats.reset();
+    }
+
+    pub fn with_capacity(capacity: usize, branching_factor: Option<u64>) -> Self {
+        DHeap {
+            elements: Vec::with_capacity(capacity),
+            element_positions: HashMap::with_capacity(capacity),
+            stats: StatsRecorder::new(),
+            branch_factor: branching_factor.unwrap_or(2),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.elements.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.elements.reserve(additional);
+        self.element_positions.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.elements.shrink_to_fit();
+        self.element_positions.shrink_to_fit();
+    }
+
+    pub fn clear(&mut self) {
+        self.elements.clear();
+        self.element_positions.clear();
+    }
+
+    /// Keeps only the elements matching `predicate`, rebuilding the heap and
+    /// its position map once afterwards if anything was removed.
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, predicate: F) {
+        let size = self.elements.len();
+        self.elements.retain(predicate);
+        if self.elements.len() < size {
+            self.element_positions.clear();
+            self.heapify();
+        }
+    }
+
+    /// Removes every element, yielding them in heap order rather than sorted
+    /// order.
+    pub fn drain(&mut self) -> std::vec::Drain<'_, T> {
+        self.element_positions.clear();
+        self.elements.drain(..)
+    }
+}
+
+impl<T> Render for DHeap<T>
+where
+    T: Eq + Hash + Clone + PartialOrd + std::fmt::Debug,
+{
+    fn to_dot(&self) -> String {
+        render::to_dot("DHeap", &self.elements, self.branch_factor as usize)
+    }
+
+    fn ascii_tree(&self) -> String {
+        render::ascii_tree(&self.elements, self.branch_factor as usize)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct StoredDHeapRef<'a, T> {
+    branch_factor: u64,
+    elements: &'a [T],
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct StoredDHeap<T> {
+    branch_factor: u64,
+    elements: Vec<T>,
+}
+
+#[cfg(feature = "serde")]
+impl<T> serde::Serialize for DHeap<T>
+where
+    T: Eq + Hash + Clone + PartialOrd + std::fmt::Debug + serde::Serialize,
+{
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoredDHeapRef {
+            branch_factor: self.branch_factor,
+            elements: &self.elements,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T> serde::Deserialize<'de> for DHeap<T>
+where
+    T: Eq + Hash + Clone + PartialOrd + std::fmt::Debug + serde::Deserialize<'de>,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let stored = StoredDHeap::<T>::deserialize(deserializer)?;
+        if stored.branch_factor == 0 {
+            return Err(serde::de::Error::custom("branch_factor must not be zero"));
+        }
+        let mut d_heap = DHeap {
+            elements: stored.elements,
+            element_positions: HashMap::new(),
+            stats: StatsRecorder::new(),
+            branch_factor: stored.branch_factor,
+        };
+        let in_order = (1..d_heap.elements.len())
+            .all(|index| d_heap.elements[index] <= d_heap.elements[d_heap.get_parent_index(index)]);
+        if !in_order {
+            return Err(serde::de::Error::custom("elements are not in heap order"));
+        }
+        for (index, element) in d_heap.elements.iter().enumerate() {
+            if d_heap
+                .element_positions
+                .insert(element.clone(), index)
+                .is_some()
+            {
+                return Err(serde::de::Error::custom("elements are not distinct"));
+            }
+        }
+        Ok(d_heap)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T> DHeap<T>
+where
+    T: Eq + Hash + Clone + PartialOrd + std::fmt::Debug,
+{
+    /// Deserializes the same `{"branch_factor": .., "elements": [..]}` struct
+    /// with distinct elements in any order and heapifies them, for use with
+    /// `#[serde(deserialize_with = "DHeap::deserialize_reheapify")]`.
+    pub fn deserialize_reheapify<'de, D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        T: serde::Deserialize<'de>,
+    {
+        use serde::Deserialize;
+        let stored = StoredDHeap::<T>::deserialize(deserializer)?;
+        if stored.branch_factor == 0 {
+            return Err(serde::de::Error::custom("branch_factor must not be zero"));
+        }
+        let size = stored.elements.len();
+        let d_heap = DHeap::new(stored.elements, Some(stored.branch_factor));
+        if d_heap.element_positions.len() < size {
+            return Err(serde::de::Error::custom("elements are not distinct"));
+        }
+        Ok(d_heap)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    use crate::heaps::test_support::d_heap_variant_tests;
+
     fn create_heap() -> DHeap<u64> {
         DHeap::new(vec![9, 10, 3, 5, 7, 8, 13], Some(3))
     }
 
+    d_heap_variant_tests!(|capacity| DHeap::<u64>::with_capacity(capacity, Some(2)));
+
     #[test]
     fn it_should_have_expected_order() {
         let expected_ordering = vec![13, 10, 9, 8, 7, 5, 3];
@@ -232,4 +465,59 @@ mod tests {
         }
         assert!(heap.is_empty());
     }
+
+    #[test]
+    fn it_should_keep_positions_in_sync_with_elements() {
+        let mut heap: DHeap<u64> = DHeap::new(vec![], None);
+        assert_eq!(None, heap.top());
+        heap.remove(1);
+        heap.insert(49);
+        heap.insert(53);
+        heap.update(49, 10);
+        assert!(!heap.contains(&49));
+        assert_eq!(Some(&53), heap.peek());
+        assert_eq!(Some(53), heap.top());
+        assert!(!heap.contains(&53));
+        heap.remove(10);
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_refuse_equal_elements() {
+        let mut heap: DHeap<u64> = DHeap::new(vec![1, 4, 1], Some(3));
+        assert_eq!(2, heap.size());
+        assert!(!heap.insert(4));
+        assert!(heap.insert(7));
+        assert!(!heap.update(1, 7));
+        assert!(!heap.update(2, 3));
+        assert!(heap.update(4, 4));
+        assert!(heap.update(1, 5));
+        let popped: Vec<u64> = (0..heap.size()).map(|_| heap.top().unwrap()).collect();
+        assert_eq!(vec![7, 5, 4], popped);
+        assert_eq!(None, heap.top());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_should_round_trip_through_serde() {
+        let heap = create_heap();
+        let json = serde_json::to_string(&heap).unwrap();
+        let mut restored: DHeap<u64> = serde_json::from_str(&json).unwrap();
+        assert!(restored.contains(&3));
+        restored.update(3, 14);
+        assert_eq!(Some(14), restored.top());
+        assert_eq!(Some(13), restored.top());
+
+        let unordered = r#"{"branch_factor": 2, "elements": [1, 2, 3]}"#;
+        assert!(serde_json::from_str::<DHeap<u64>>(unordered).is_err());
+        let mut deserializer = serde_json::Deserializer::from_str(unordered);
+        let mut reheapified: DHeap<u64> = DHeap::deserialize_reheapify(&mut deserializer).unwrap();
+        assert!(reheapified.contains(&1));
+        assert_eq!(Some(3), reheapified.top());
+
+        let duplicated = r#"{"branch_factor": 2, "elements": [1, 1]}"#;
+        assert!(serde_json::from_str::<DHeap<u64>>(duplicated).is_err());
+        let mut deserializer = serde_json::Deserializer::from_str(duplicated);
+        assert!(DHeap::<u64>::deserialize_reheapify(&mut deserializer).is_err());
+    }
 }
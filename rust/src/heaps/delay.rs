@@ -0,0 +1,403 @@
+use std::{
+    cell::Cell,
+    cmp::Reverse,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use super::d_heap::DHeap;
+
+/// Source of the current time for a [`DelayQueue`], so tests can drive time
+/// by hand instead of sleeping.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Blocks until `deadline` has been reached.
+    fn sleep_until(&self, deadline: Instant);
+}
+
+/// The real clock, backed by [`Instant::now`] and [`std::thread::sleep`].
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let now = Instant::now();
+        if deadline > now {
+            std::thread::sleep(deadline - now);
+        }
+    }
+}
+
+/// A clock that only moves when told to. Sleeping jumps straight to the
+/// deadline. Clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Rc<Cell<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new(start: Instant) -> Self {
+        ManualClock {
+            now: Rc::new(Cell::new(start)),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        if deadline > self.now.get() {
+            self.now.set(deadline);
+        }
+    }
+}
+
+/// Identifies an item in a [`DelayQueue`] for `reset` and `cancel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DelayKey {
+    index: usize,
+    stamp: u64,
+}
+
+/// An item whose deadline has passed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Expired<T> {
+    pub key: DelayKey,
+    pub deadline: Instant,
+    pub item: T,
+}
+
+struct Slot<T> {
+    // stamp of the key handed out for this slot
+    key_stamp: u64,
+    // stamp of the heap entry that is currently live for this slot
+    heap_stamp: u64,
+    entry: Option<(Instant, T)>,
+}
+
+type HeapEntry = Reverse<(Instant, u64, usize)>;
+
+/// Longest delay [`DelayQueue::insert_after`] honours, since an item this far
+/// out never expires in practice anyway.
+pub const MAX_DELAY: Duration = Duration::from_secs(86_400 * 365 * 30);
+
+/// `now + delay`, saturating at the latest `Instant` after `now` that the
+/// platform can represent.
+fn deadline_after(now: Instant, delay: Duration) -> Instant {
+    if let Some(deadline) = now.checked_add(delay) {
+        return deadline;
+    }
+    // binary search the longest delay that still fits
+    let (mut fits, mut overflows) = (Duration::ZERO, delay);
+    while overflows - fits > Duration::from_nanos(1) {
+        let middle = fits + (overflows - fits) / 2;
+        if now.checked_add(middle).is_some() {
+            fits = middle;
+        } else {
+            overflows = middle;
+        }
+    }
+    now + fits
+}
+
+/// Items that become available once their deadline passes, ordered by a
+/// min-ordered [`DHeap`] of deadlines.
+///
+/// Items live in a slab and the heap only holds `(deadline, stamp, slot)`
+/// entries. `reset` and `cancel` leave the old entry behind to be skipped
+/// when it surfaces, and the heap is rebuilt once such stale entries
+/// outnumber the live ones.
+pub struct DelayQueue<T, C: Clock = SystemClock> {
+    heap: DHeap<HeapEntry, 4>,
+    slots: Vec<Slot<T>>,
+    free_slots: Vec<usize>,
+    next_stamp: u64,
+    size: usize,
+    clock: C,
+}
+
+impl<T> DelayQueue<T> {
+    pub fn new() -> Self {
+        DelayQueue::with_clock(SystemClock)
+    }
+}
+
+impl<T> Default for DelayQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, C: Clock> DelayQueue<T, C> {
+    pub fn with_clock(clock: C) -> Self {
+        DelayQueue {
+            heap: DHeap::new(vec![]),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            next_stamp: 0,
+            size: 0,
+            clock,
+        }
+    }
+
+    pub fn insert_at(&mut self, item: T, deadline: Instant) -> DelayKey {
+        let key_stamp = self.stamp();
+        let slot = Slot {
+            key_stamp,
+            heap_stamp: key_stamp,
+            entry: Some((deadline, item)),
+        };
+        let index = match self.free_slots.pop() {
+            Some(index) => {
+                self.slots[index] = slot;
+                index
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        };
+        self.heap.insert(Reverse((deadline, key_stamp, index)));
+        self.size += 1;
+        DelayKey {
+            index,
+            stamp: key_stamp,
+        }
+    }
+
+    /// Queues `item` to expire `delay` from now, capping the delay at
+    /// [`MAX_DELAY`]. A deadline past the latest `Instant` the platform can
+    /// hold, as with a clock that is already near it, saturates there.
+    pub fn insert_after(&mut self, item: T, delay: Duration) -> DelayKey {
+        let deadline = deadline_after(self.clock.now(), delay.min(MAX_DELAY));
+        self.insert_at(item, deadline)
+    }
+
+    /// Moves the deadline of the item behind `key`. Returns false if the item
+    /// has already expired or been cancelled.
+    pub fn reset(&mut self, key: DelayKey, deadline: Instant) -> bool {
+        let heap_stamp = self.stamp();
+        let Some(slot) = self.live_slot(key) else {
+            return false;
+        };
+        slot.heap_stamp = heap_stamp;
+        if let Some(entry) = slot.entry.as_mut() {
+            entry.0 = deadline;
+        }
+        self.heap.insert(Reverse((deadline, heap_stamp, key.index)));
+        self.compact();
+        true
+    }
+
+    /// Removes the item behind `key`, returning it if it was still queued.
+    pub fn cancel(&mut self, key: DelayKey) -> Option<T> {
+        let (_, item) = self.live_slot(key)?.entry.take()?;
+        self.free_slots.push(key.index);
+        self.size -= 1;
+        self.compact();
+        Some(item)
+    }
+
+    /// Removes and returns an item whose deadline is at or before `now`.
+    pub fn poll_expired(&mut self, now: Instant) -> Option<Expired<T>> {
+        let (deadline, index) = self.next_deadline()?;
+        if deadline > now {
+            return None;
+        }
+        self.heap.top();
+        let slot = &mut self.slots[index];
+        let (deadline, item) = slot.entry.take()?;
+        self.free_slots.push(index);
+        self.size -= 1;
+        Some(Expired {
+            key: DelayKey {
+                index,
+                stamp: slot.key_stamp,
+            },
+            deadline,
+            item,
+        })
+    }
+
+    /// Blocks on the clock until the earliest item expires and returns it, or
+    /// returns `None` straight away if the queue is empty.
+    pub fn next_expired(&mut self) -> Option<Expired<T>> {
+        let (deadline, _) = self.next_deadline()?;
+        self.clock.sleep_until(deadline);
+        self.poll_expired(deadline)
+    }
+
+    /// Returns the earliest deadline in the queue.
+    pub fn peek_deadline(&mut self) -> Option<Instant> {
+        self.next_deadline().map(|(deadline, _)| deadline)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    fn stamp(&mut self) -> u64 {
+        self.next_stamp += 1;
+        self.next_stamp
+    }
+
+    fn live_slot(&mut self, key: DelayKey) -> Option<&mut Slot<T>> {
+        self.slots
+            .get_mut(key.index)
+            .filter(|slot| slot.key_stamp == key.stamp && slot.entry.is_some())
+    }
+
+    /// Drops stale entries off the top of the heap and returns the live one.
+    fn next_deadline(&mut self) -> Option<(Instant, usize)> {
+        while let Some(&Reverse((deadline, stamp, index))) = self.heap.peek() {
+            let slot = &self.slots[index];
+            if slot.entry.is_some() && slot.heap_stamp == stamp {
+                return Some((deadline, index));
+            }
+            self.heap.top();
+        }
+        None
+    }
+
+    /// Rebuild the heap from the slab once stale entries dominate it.
+    fn compact(&mut self) {
+        if self.heap.size() <= 2 * self.size + 16 {
+            return;
+        }
+        let entries = self
+            .slots
+            .iter()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let (deadline, _) = slot.entry.as_ref()?;
+                Some(Reverse((*deadline, slot.heap_stamp, index)))
+            })
+            .collect();
+        self.heap = DHeap::new(entries);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_queue() -> (DelayQueue<&'static str, ManualClock>, Instant) {
+        let start = Instant::now();
+        let mut queue = DelayQueue::with_clock(ManualClock::new(start));
+        queue.insert_after("c", Duration::from_secs(30));
+        queue.insert_after("a", Duration::from_secs(10));
+        queue.insert_at("b", start + Duration::from_secs(20));
+        (queue, start)
+    }
+
+    #[test]
+    fn it_should_expire_items_in_deadline_order() {
+        let (mut queue, start) = create_queue();
+        assert_eq!(None, queue.poll_expired(start + Duration::from_secs(5)));
+        let expired = queue.poll_expired(start + Duration::from_secs(25)).unwrap();
+        assert_eq!(
+            ("a", start + Duration::from_secs(10)),
+            (expired.item, expired.deadline)
+        );
+        assert_eq!(
+            "b",
+            queue
+                .poll_expired(start + Duration::from_secs(25))
+                .unwrap()
+                .item
+        );
+        assert_eq!(None, queue.poll_expired(start + Duration::from_secs(25)));
+        assert_eq!(1, queue.size());
+    }
+
+    #[test]
+    fn it_should_cap_delays_that_overflow_an_instant() {
+        let (mut queue, start) = create_queue();
+        queue.insert_after("never", Duration::MAX);
+        assert_eq!(4, queue.size());
+        let items: Vec<_> =
+            std::iter::from_fn(|| queue.poll_expired(start + Duration::from_secs(60)))
+                .map(|expired| expired.item)
+                .collect();
+        assert_eq!(vec!["a", "b", "c"], items);
+        assert_eq!(Some(start + MAX_DELAY), queue.peek_deadline());
+    }
+
+    #[test]
+    fn it_should_saturate_deadlines_near_the_latest_instant() {
+        let latest = deadline_after(Instant::now(), Duration::MAX);
+        assert_eq!(None, latest.checked_add(Duration::from_nanos(1)));
+        let mut queue = DelayQueue::with_clock(ManualClock::new(latest - Duration::from_secs(1)));
+        queue.insert_after("late", Duration::from_secs(60));
+        assert_eq!(Some(latest), queue.peek_deadline());
+        let expired = queue.poll_expired(latest).map(|expired| expired.item);
+        assert_eq!(Some("late"), expired);
+    }
+
+    #[test]
+    fn it_should_reset_and_cancel_items() {
+        let (mut queue, start) = create_queue();
+        let d = queue.insert_after("d", Duration::from_secs(40));
+        let e = queue.insert_after("e", Duration::from_secs(50));
+        assert!(queue.reset(d, start + Duration::from_secs(1)));
+        assert_eq!(Some("e"), queue.cancel(e));
+        assert_eq!(None, queue.cancel(e));
+        assert!(!queue.reset(e, start));
+        let items: Vec<_> =
+            std::iter::from_fn(|| queue.poll_expired(start + Duration::from_secs(60)))
+                .map(|expired| expired.item)
+                .collect();
+        assert_eq!(vec!["d", "a", "b", "c"], items);
+        assert!(queue.is_empty());
+        assert!(!queue.reset(d, start));
+    }
+
+    #[test]
+    fn it_should_advance_the_clock_when_blocking_for_the_next_item() {
+        let (mut queue, start) = create_queue();
+        assert_eq!("a", queue.next_expired().unwrap().item);
+        assert_eq!(start + Duration::from_secs(10), queue.clock().now());
+        queue.clock().advance(Duration::from_secs(15));
+        assert_eq!("b", queue.next_expired().unwrap().item);
+        assert_eq!(start + Duration::from_secs(25), queue.clock().now());
+        assert_eq!("c", queue.next_expired().unwrap().item);
+        assert_eq!(None, queue.next_expired());
+    }
+
+    #[test]
+    fn it_should_compact_stale_entries_after_many_resets() {
+        let (mut queue, start) = create_queue();
+        let key = queue.insert_after("d", Duration::from_secs(40));
+        for second in 0..1_000 {
+            assert!(queue.reset(key, start + Duration::from_secs(100 + second)));
+        }
+        assert!(queue.heap.size() <= 2 * queue.size() + 16);
+        assert_eq!(Some(start + Duration::from_secs(10)), queue.peek_deadline());
+        queue.clock().advance(Duration::from_secs(2_000));
+        let now = queue.clock().now();
+        let items: Vec<_> = std::iter::from_fn(|| queue.poll_expired(now))
+            .map(|expired| expired.item)
+            .collect();
+        assert_eq!(vec!["a", "b", "c", "d"], items);
+    }
+}
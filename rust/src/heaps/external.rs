@@ -0,0 +1,454 @@
+use std::{
+    cmp::Ordering,
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    sync::atomic::{AtomicU64, Ordering as AtomicOrdering},
+};
+
+use super::d_heap::DHeap;
+
+/// Turns elements into bytes and back for spilling them to disk.
+pub trait Codec<T> {
+    fn encode(&self, element: &T, writer: &mut dyn Write) -> io::Result<()>;
+
+    /// Reads the next element, or `None` at a clean end of input. Input that
+    /// ends partway through an element is an error.
+    fn decode(&self, reader: &mut dyn Read) -> io::Result<Option<T>>;
+}
+
+/// Encodes primitive integers as little-endian bytes.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct LeBytesCodec;
+
+macro_rules! impl_le_bytes_codec {
+    ($($t:ty),*) => {
+        $(
+            impl Codec<$t> for LeBytesCodec {
+                fn encode(&self, element: &$t, writer: &mut dyn Write) -> io::Result<()> {
+                    writer.write_all(&element.to_le_bytes())
+                }
+
+                fn decode(&self, reader: &mut dyn Read) -> io::Result<Option<$t>> {
+                    let mut bytes = [0; std::mem::size_of::<$t>()];
+                    Ok(read_element(reader, &mut bytes)?.then(|| <$t>::from_le_bytes(bytes)))
+                }
+            }
+        )*
+    };
+}
+
+/// Fills `bytes`, returning false if the input ends before the first byte.
+fn read_element(reader: &mut dyn Read, bytes: &mut [u8]) -> io::Result<bool> {
+    let mut filled = 0;
+    while filled < bytes.len() {
+        match reader.read(&mut bytes[filled..]) {
+            Ok(0) if filled == 0 => return Ok(false),
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "input ends partway through an element",
+                ))
+            }
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(true)
+}
+
+impl_le_bytes_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+/// A sorted run spilled to a temporary file, deleted when dropped.
+struct Run {
+    path: PathBuf,
+    reader: BufReader<File>,
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The next unread element of a run, ordered by element for the merge heap.
+struct RunHead<T> {
+    element: T,
+    run: usize,
+}
+
+impl<T: Ord> Ord for RunHead<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.element.cmp(&other.element)
+    }
+}
+
+impl<T: Ord> PartialOrd for RunHead<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> PartialEq for RunHead<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.element == other.element
+    }
+}
+
+impl<T: Ord> Eq for RunHead<T> {}
+
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A max-ordered priority queue that can hold more elements than fit in
+/// memory.
+///
+/// New elements go into an in-memory [`DHeap`] buffer. Once the buffer holds
+/// `memory_budget` elements it is drained, already sorted, into a run in a
+/// temporary file. `top` compares the buffer against a second `B`-ary heap
+/// holding the head of every run, so each element is read back exactly once.
+/// A run's file is closed and deleted as soon as it has been read back.
+///
+/// No element is lost to an I/O error: a failed spill keeps its elements in
+/// the buffer, and a failed read keeps the run's head in the heap.
+pub struct ExternalDHeap<T: Ord, C: Codec<T>, const B: usize = 4> {
+    buffer: DHeap<T, B>,
+    run_heads: DHeap<RunHead<T>, B>,
+    // indexed by `RunHead::run`, `None` once a run has been read back
+    runs: Vec<Option<Run>>,
+    codec: C,
+    memory_budget: usize,
+    directory: PathBuf,
+    size: usize,
+}
+
+impl<T: Ord, C: Codec<T>, const B: usize> ExternalDHeap<T, C, B> {
+    /// Creates a heap keeping at most `memory_budget` buffered elements in
+    /// memory and spilling runs to the system temporary directory.
+    pub fn new(codec: C, memory_budget: usize) -> Self {
+        Self::with_directory(codec, memory_budget, std::env::temp_dir())
+    }
+
+    /// Like [`new`](Self::new) but spilling runs into `directory`.
+    pub fn with_directory(codec: C, memory_budget: usize, directory: PathBuf) -> Self {
+        ExternalDHeap {
+            buffer: DHeap::new(vec![]),
+            run_heads: DHeap::new(vec![]),
+            runs: Vec::new(),
+            codec,
+            memory_budget: std::cmp::max(memory_budget, 1),
+            directory,
+            size: 0,
+        }
+    }
+
+    /// Inserts `element`, spilling the buffer first if it is full. If the
+    /// spill fails, `element` is still buffered, over budget, and the error is
+    /// returned; the next insert tries the spill again.
+    pub fn insert(&mut self, element: T) -> io::Result<()> {
+        let spilled = if self.buffer.size() >= self.memory_budget {
+            self.spill()
+        } else {
+            Ok(())
+        };
+        self.buffer.insert(element);
+        self.size += 1;
+        spilled
+    }
+
+    pub fn top(&mut self) -> io::Result<Option<T>> {
+        let from_runs = match (self.buffer.peek(), self.run_heads.peek()) {
+            (_, None) => false,
+            (None, Some(_)) => true,
+            (Some(buffered), Some(head)) => head.element > *buffered,
+        };
+        let element = if from_runs {
+            let head = self.run_heads.top().expect("peeked a run head");
+            let run = self.runs[head.run].as_mut().expect("a run head has a run");
+            match self.codec.decode(&mut run.reader) {
+                Ok(Some(next)) => self.run_heads.insert(RunHead {
+                    element: next,
+                    run: head.run,
+                }),
+                Ok(None) => self.runs[head.run] = None,
+                Err(e) => {
+                    self.run_heads.insert(head);
+                    return Err(e);
+                }
+            }
+            Some(head.element)
+        } else {
+            self.buffer.top()
+        };
+        if element.is_some() {
+            self.size -= 1;
+        }
+        if self.run_heads.is_empty() {
+            self.runs.clear();
+        }
+        Ok(element)
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        match (self.buffer.peek(), self.run_heads.peek()) {
+            (Some(buffered), Some(head)) if head.element > *buffered => Some(&head.element),
+            (None, Some(head)) => Some(&head.element),
+            (buffered, _) => buffered,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of runs currently spilled to disk.
+    pub fn run_count(&self) -> usize {
+        self.runs.iter().flatten().count()
+    }
+
+    /// Write the buffer out as a descending run and start reading it back. On
+    /// error the buffer is put back as it was.
+    fn spill(&mut self) -> io::Result<()> {
+        let mut elements: Vec<T> = self.buffer.drain().collect();
+        elements.sort_unstable_by(|a, b| b.cmp(a));
+        match self.write_run(&elements) {
+            Ok((run, head)) => {
+                let index = match self.runs.iter().position(Option::is_none) {
+                    Some(index) => index,
+                    None => {
+                        self.runs.push(None);
+                        self.runs.len() - 1
+                    }
+                };
+                self.runs[index] = Some(run);
+                self.run_heads.insert(RunHead {
+                    element: head,
+                    run: index,
+                });
+                Ok(())
+            }
+            Err(e) => {
+                // sorted descending, so each insert stays where it lands
+                for element in elements {
+                    self.buffer.insert(element);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Writes `elements` to a new run and reads back its first element.
+    fn write_run(&self, elements: &[T]) -> io::Result<(Run, T)> {
+        let id = NEXT_RUN_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        let path =
+            self.directory
+                .join(format!("external-d-heap-{}-{}.run", std::process::id(), id));
+        let write_run = || -> io::Result<(BufReader<File>, T)> {
+            let file = OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?;
+            let mut writer = BufWriter::new(file);
+            for element in elements {
+                self.codec.encode(element, &mut writer)?;
+            }
+            writer.flush()?;
+            let mut reader = BufReader::new(File::open(&path)?);
+            let head = self.codec.decode(&mut reader)?.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::UnexpectedEof, "spilled run is empty")
+            })?;
+            Ok((reader, head))
+        };
+        match write_run() {
+            Ok((reader, head)) => Ok((Run { path, reader }, head)),
+            Err(e) => {
+                let _ = fs::remove_file(&path);
+                Err(e)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    fn create_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "external-d-heap-test-{}-{}",
+            name,
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    /// A [`LeBytesCodec`] that fails once its encode or decode budget runs out.
+    #[derive(Default, Clone)]
+    struct FailingCodec {
+        encodes_left: Rc<Cell<usize>>,
+        decodes_left: Rc<Cell<usize>>,
+    }
+
+    impl FailingCodec {
+        fn new() -> Self {
+            let codec = FailingCodec::default();
+            codec.encodes_left.set(usize::MAX);
+            codec.decodes_left.set(usize::MAX);
+            codec
+        }
+    }
+
+    fn use_up(budget: &Cell<usize>) -> io::Result<()> {
+        if budget.get() == 0 {
+            return Err(io::Error::other("budget used up"));
+        }
+        budget.set(budget.get() - 1);
+        Ok(())
+    }
+
+    impl Codec<u64> for FailingCodec {
+        fn encode(&self, element: &u64, writer: &mut dyn Write) -> io::Result<()> {
+            use_up(&self.encodes_left)?;
+            LeBytesCodec.encode(element, writer)
+        }
+
+        fn decode(&self, reader: &mut dyn Read) -> io::Result<Option<u64>> {
+            use_up(&self.decodes_left)?;
+            LeBytesCodec.decode(reader)
+        }
+    }
+
+    fn drain<C: Codec<u64>>(heap: &mut ExternalDHeap<u64, C, 3>) -> Vec<u64> {
+        std::iter::from_fn(|| heap.top().unwrap()).collect()
+    }
+
+    #[test]
+    fn it_should_have_expected_order_across_runs() {
+        let directory = create_directory("order");
+        let mut heap: ExternalDHeap<u64, _, 3> =
+            ExternalDHeap::with_directory(LeBytesCodec, 3, directory.clone());
+        for number in [9, 10, 9, 8, 7, 5, 3, 8] {
+            heap.insert(number).unwrap();
+        }
+        assert_eq!(2, heap.run_count());
+        assert_eq!(Some(&10), heap.peek());
+        let mut ordering = Vec::new();
+        while let Some(number) = heap.top().unwrap() {
+            ordering.push(number);
+        }
+        assert_eq!(vec![10, 9, 9, 8, 8, 7, 5, 3], ordering);
+        assert!(heap.is_empty());
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn it_should_match_a_sorted_model_when_interleaving_operations() {
+        let directory = create_directory("interleaved");
+        let mut heap: ExternalDHeap<i64, _> =
+            ExternalDHeap::with_directory(LeBytesCodec, 64, directory.clone());
+        let mut model: Vec<i64> = Vec::new();
+        for n in 0..5_000i64 {
+            let value = (n * 7_919) % 1_009 - 500;
+            heap.insert(value).unwrap();
+            model.push(value);
+            if n % 3 == 0 {
+                model.sort();
+                assert_eq!(model.pop(), heap.top().unwrap());
+            }
+        }
+        assert!(heap.run_count() > 1);
+        model.sort();
+        while let Some(expected) = model.pop() {
+            assert_eq!(Some(expected), heap.top().unwrap());
+        }
+        assert_eq!(None, heap.top().unwrap());
+        drop(heap);
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn it_should_delete_each_run_once_it_is_read_back() {
+        let directory = create_directory("delete");
+        let mut heap: ExternalDHeap<u64, _, 3> =
+            ExternalDHeap::with_directory(LeBytesCodec, 2, directory.clone());
+        for number in [10, 9, 1, 2, 3] {
+            heap.insert(number).unwrap();
+        }
+        assert_eq!(2, heap.run_count());
+        assert_eq!(Some(10), heap.top().unwrap());
+        assert_eq!(Some(9), heap.top().unwrap());
+        assert_eq!(1, heap.run_count());
+        assert_eq!(1, fs::read_dir(&directory).unwrap().count());
+        heap.insert(4).unwrap();
+        heap.insert(5).unwrap();
+        assert_eq!(2, heap.run_count());
+        assert_eq!(vec![5, 4, 3, 2, 1], drain(&mut heap));
+        assert_eq!(0, heap.run_count());
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn it_should_keep_the_buffer_when_a_spill_fails() {
+        let directory = create_directory("spill");
+        let codec = FailingCodec::new();
+        let mut heap: ExternalDHeap<u64, _, 3> =
+            ExternalDHeap::with_directory(codec.clone(), 3, directory.clone());
+        for number in [9, 10, 9] {
+            heap.insert(number).unwrap();
+        }
+        codec.encodes_left.set(1);
+        assert!(heap.insert(8).is_err());
+        assert_eq!((4, 0), (heap.size(), heap.run_count()));
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+
+        codec.encodes_left.set(usize::MAX);
+        codec.decodes_left.set(0);
+        assert!(heap.insert(7).is_err());
+        assert_eq!((5, 0), (heap.size(), heap.run_count()));
+        assert_eq!(0, fs::read_dir(&directory).unwrap().count());
+
+        codec.decodes_left.set(usize::MAX);
+        heap.insert(3).unwrap();
+        assert_eq!((6, 1), (heap.size(), heap.run_count()));
+        assert_eq!(vec![10, 9, 9, 8, 7, 3], drain(&mut heap));
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn it_should_keep_the_run_head_when_a_read_fails() {
+        let directory = create_directory("read");
+        let codec = FailingCodec::new();
+        let mut heap: ExternalDHeap<u64, _, 3> =
+            ExternalDHeap::with_directory(codec.clone(), 2, directory.clone());
+        for number in [10, 9, 1] {
+            heap.insert(number).unwrap();
+        }
+        codec.decodes_left.set(0);
+        assert!(heap.top().is_err());
+        assert_eq!(3, heap.size());
+        assert_eq!(Some(&10), heap.peek());
+
+        codec.decodes_left.set(usize::MAX);
+        assert_eq!(vec![10, 9, 1], drain(&mut heap));
+        assert!(heap.is_empty());
+        fs::remove_dir(&directory).unwrap();
+    }
+
+    #[test]
+    fn it_should_report_a_truncated_element() {
+        let decode = |bytes: &[u8]| Codec::<u32>::decode(&LeBytesCodec, &mut &*bytes);
+        assert_eq!(Some(0x0403_0201), decode(&[1, 2, 3, 4]).unwrap());
+        assert_eq!(None, decode(&[]).unwrap());
+        let error = decode(&[1, 2, 3]).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+    }
+}
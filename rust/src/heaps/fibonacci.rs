@@ -0,0 +1,402 @@
+use alloc::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::cell::{Ref, RefCell};
+
+use super::heap_id::HeapId;
+
+type NodeLink<T> = Rc<RefCell<FibonacciNode<T>>>;
+type WeakLink<T> = Weak<RefCell<FibonacciNode<T>>>;
+
+struct FibonacciNode<T> {
+    element: T,
+    parent: Option<WeakLink<T>>,
+    child: Option<NodeLink<T>>,
+    next: Option<NodeLink<T>>,
+    // None for the first node of a list
+    prev: Option<WeakLink<T>>,
+    degree: usize,
+    marked: bool,
+}
+
+/// A reference to an element in a [`FibonacciHeap`], used for `decrease_key`.
+///
+/// Handles don't keep their element alive, so a handle to an element that has
+/// since been popped is simply stale.
+pub struct Handle<T>(WeakLink<T>, HeapId);
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(self.0.clone(), self.1.clone())
+    }
+}
+
+/// A max-ordered Fibonacci heap with O(1) `insert` and `meld`, O(1) amortized
+/// `decrease_key` and O(log n) amortized `top`.
+pub struct FibonacciHeap<T: Ord> {
+    roots: Option<NodeLink<T>>,
+    last_root: Option<WeakLink<T>>,
+    top: Option<NodeLink<T>>,
+    size: usize,
+    id: HeapId,
+}
+
+impl<T: Ord> FibonacciHeap<T> {
+    pub fn new() -> Self {
+        FibonacciHeap {
+            roots: None,
+            last_root: None,
+            top: None,
+            size: 0,
+            id: HeapId::default(),
+        }
+    }
+
+    pub fn insert(&mut self, element: T) -> Handle<T> {
+        let node = Rc::new(RefCell::new(FibonacciNode {
+            element,
+            parent: None,
+            child: None,
+            next: None,
+            prev: None,
+            degree: 0,
+            marked: false,
+        }));
+        let handle = Handle(Rc::downgrade(&node), self.id.clone());
+        self.push_root(node);
+        self.size += 1;
+        handle
+    }
+
+    pub fn top(&mut self) -> Option<T> {
+        let top = self.top.take()?;
+        self.remove_root(&top);
+        let mut next_child = top.borrow_mut().child.take();
+        while let Some(child) = next_child {
+            next_child = child.borrow_mut().next.take();
+            child.borrow_mut().prev = None;
+            self.push_root(child);
+        }
+        self.consolidate();
+        self.size -= 1;
+        let node = Rc::try_unwrap(top)
+            .ok()
+            .expect("Heap nodes should only be owned by the heap");
+        Some(node.into_inner().element)
+    }
+
+    pub fn peek(&self) -> Option<Ref<'_, T>> {
+        self.top
+            .as_ref()
+            .map(|top| Ref::map(top.borrow(), |node| &node.element))
+    }
+
+    /// Moves all elements of `other` into this heap in O(1).
+    pub fn meld(&mut self, mut other: FibonacciHeap<T>) {
+        other.id.forward_to(&self.id);
+        let Some(other_roots) = other.roots.take() else {
+            return;
+        };
+        match self.last_root.as_ref().and_then(Weak::upgrade) {
+            Some(last_root) => {
+                other_roots.borrow_mut().prev = Some(Rc::downgrade(&last_root));
+                last_root.borrow_mut().next = Some(other_roots);
+            }
+            None => self.roots = Some(other_roots),
+        }
+        self.last_root = other.last_root.take();
+        let other_top = other.top.take().expect("a non-empty heap has a top");
+        self.update_top(other_top);
+        self.size += other.size;
+    }
+
+    /// Replaces the element behind `handle` with `element`, which must rank at
+    /// least as high, moving it towards the top. With `Reverse` priorities this
+    /// is a literal decrease of the key.
+    ///
+    /// Returns false, leaving the heap untouched, if the handle is stale or
+    /// belongs to another heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element` ranks below the current element.
+    pub fn decrease_key(&mut self, handle: &Handle<T>, element: T) -> bool {
+        if !handle.1.is(&self.id) {
+            return false;
+        }
+        let Some(node) = handle.0.upgrade() else {
+            return false;
+        };
+        assert!(
+            element >= node.borrow().element,
+            "decrease_key must not move an element away from the top"
+        );
+        node.borrow_mut().element = element;
+        let parent = node.borrow().parent.as_ref().and_then(Weak::upgrade);
+        match parent {
+            Some(parent) if node.borrow().element > parent.borrow().element => {
+                remove_child(&node);
+                self.push_root(node);
+                self.cascading_cut(parent);
+            }
+            Some(_) => {}
+            None => self.update_top(node),
+        }
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn update_top(&mut self, node: NodeLink<T>) {
+        let is_new_top = match &self.top {
+            Some(top) => node.borrow().element > top.borrow().element,
+            None => true,
+        };
+        if is_new_top {
+            self.top = Some(node);
+        }
+    }
+
+    fn push_root(&mut self, node: NodeLink<T>) {
+        {
+            let mut node_ref = node.borrow_mut();
+            node_ref.parent = None;
+            node_ref.marked = false;
+            node_ref.next = None;
+            node_ref.prev = self.last_root.clone();
+        }
+        match self.last_root.as_ref().and_then(Weak::upgrade) {
+            Some(last_root) => last_root.borrow_mut().next = Some(node.clone()),
+            None => self.roots = Some(node.clone()),
+        }
+        self.last_root = Some(Rc::downgrade(&node));
+        self.update_top(node);
+    }
+
+    fn remove_root(&mut self, node: &NodeLink<T>) {
+        let mut node_ref = node.borrow_mut();
+        let prev = node_ref.prev.take();
+        let next = node_ref.next.take();
+        match &next {
+            Some(next) => next.borrow_mut().prev = prev.clone(),
+            None => self.last_root = prev.clone(),
+        }
+        match prev.as_ref().and_then(Weak::upgrade) {
+            Some(prev) => prev.borrow_mut().next = next,
+            None => self.roots = next,
+        }
+    }
+
+    /// Link roots of equal degree until every root has a distinct degree.
+    fn consolidate(&mut self) {
+        let mut by_degree: Vec<Option<NodeLink<T>>> = Vec::new();
+        let mut next_root = self.roots.take();
+        self.last_root = None;
+        self.top = None;
+        while let Some(root) = next_root {
+            next_root = root.borrow_mut().next.take();
+            root.borrow_mut().prev = None;
+            let mut root = root;
+            loop {
+                let degree = root.borrow().degree;
+                if by_degree.len() <= degree {
+                    by_degree.resize(degree + 1, None);
+                }
+                match by_degree[degree].take() {
+                    Some(other) => root = link(root, other),
+                    None => {
+                        by_degree[degree] = Some(root);
+                        break;
+                    }
+                }
+            }
+        }
+        for root in by_degree.into_iter().flatten() {
+            self.push_root(root);
+        }
+    }
+
+    fn cascading_cut(&mut self, mut node: NodeLink<T>) {
+        loop {
+            let Some(parent) = node.borrow().parent.as_ref().and_then(Weak::upgrade) else {
+                return;
+            };
+            if !node.borrow().marked {
+                node.borrow_mut().marked = true;
+                return;
+            }
+            remove_child(&node);
+            self.push_root(node);
+            node = parent;
+        }
+    }
+}
+
+impl<T: Ord> Default for FibonacciHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Drop for FibonacciHeap<T> {
+    fn drop(&mut self) {
+        // unlink iteratively, long sibling chains would otherwise overflow the
+        // stack through recursive Rc drops
+        self.top = None;
+        let mut pending: Vec<NodeLink<T>> = self.roots.take().into_iter().collect();
+        while let Some(node) = pending.pop() {
+            let mut node = node.borrow_mut();
+            pending.extend(node.child.take());
+            pending.extend(node.next.take());
+        }
+    }
+}
+
+/// Make the lower ranked of two roots a child of the other.
+fn link<T: Ord>(a: NodeLink<T>, b: NodeLink<T>) -> NodeLink<T> {
+    let (parent, child) = if b.borrow().element > a.borrow().element {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    {
+        let mut child_ref = child.borrow_mut();
+        child_ref.parent = Some(Rc::downgrade(&parent));
+        child_ref.marked = false;
+        child_ref.prev = None;
+        if let Some(first_child) = parent.borrow_mut().child.take() {
+            first_child.borrow_mut().prev = Some(Rc::downgrade(&child));
+            child_ref.next = Some(first_child);
+        }
+    }
+    let mut parent_ref = parent.borrow_mut();
+    parent_ref.child = Some(child);
+    parent_ref.degree += 1;
+    drop(parent_ref);
+    parent
+}
+
+/// Detach `node` (and its subtree) from its parent's child list.
+fn remove_child<T>(node: &NodeLink<T>) {
+    let mut node_ref = node.borrow_mut();
+    let parent = node_ref
+        .parent
+        .take()
+        .and_then(|parent| parent.upgrade())
+        .expect("remove_child is only called on nodes with a parent");
+    let prev = node_ref.prev.take();
+    let next = node_ref.next.take();
+    if let Some(next) = &next {
+        next.borrow_mut().prev = prev.clone();
+    }
+    let mut parent_ref = parent.borrow_mut();
+    match prev.as_ref().and_then(Weak::upgrade) {
+        Some(prev) => prev.borrow_mut().next = next,
+        None => parent_ref.child = next,
+    }
+    parent_ref.degree -= 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::heaps::test_support::{self, priority_queue_tests, TestHeap};
+
+    impl TestHeap for FibonacciHeap<u64> {
+        fn insert(&mut self, element: u64) {
+            FibonacciHeap::insert(self, element);
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            FibonacciHeap::top(self)
+        }
+
+        fn peek(&self) -> Option<u64> {
+            FibonacciHeap::peek(self).map(|top| *top)
+        }
+
+        fn size(&self) -> usize {
+            FibonacciHeap::size(self)
+        }
+    }
+
+    fn create_heap() -> FibonacciHeap<u64> {
+        test_support::create_heap()
+    }
+
+    priority_queue_tests!(FibonacciHeap<u64>);
+
+    #[test]
+    fn it_should_be_able_to_decrease_keys() {
+        let expected_ordering = vec![14, 12, 11, 9, 9, 8, 8, 7, 5, 3];
+        let mut heap = create_heap();
+        let handles: Vec<_> = [4, 2, 1].into_iter().map(|n| heap.insert(n)).collect();
+        heap.top();
+        assert!(heap.decrease_key(&handles[0], 14));
+        assert!(heap.decrease_key(&handles[1], 11));
+        assert!(heap.decrease_key(&handles[2], 12));
+        assert_eq!(14, *heap.peek().unwrap());
+        for number in expected_ordering {
+            let actual = heap.top().unwrap();
+            assert_eq!(actual, number);
+        }
+        assert!(!heap.decrease_key(&handles[0], 20));
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_be_able_to_meld_heaps() {
+        let expected_ordering = vec![12, 10, 9, 9, 8, 8, 7, 6, 5, 3];
+        let mut heap = create_heap();
+        let mut other = FibonacciHeap::new();
+        other.insert(6);
+        let handle = other.insert(1);
+        heap.meld(other);
+        assert!(heap.decrease_key(&handle, 12));
+        assert_eq!(10, heap.size());
+        for number in expected_ordering {
+            let actual = heap.top().unwrap();
+            assert_eq!(actual, number);
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_ignore_handles_from_other_heaps() {
+        let mut heap = FibonacciHeap::new();
+        let mut other = create_heap();
+        let handle = other.insert(1);
+        assert!(!heap.decrease_key(&handle, 12));
+        heap.insert(4);
+        assert!(!heap.decrease_key(&handle, 12));
+        assert_eq!(Some(4), heap.top());
+        assert!(heap.is_empty());
+        assert_eq!(9, other.size());
+        assert_eq!(Some(10), other.top());
+    }
+
+    #[test]
+    fn it_should_cascade_cuts_through_marked_parents() {
+        let mut heap = FibonacciHeap::new();
+        let handles: Vec<_> = (0..64u64).map(|n| heap.insert(n)).collect();
+        heap.top();
+        for (offset, handle) in handles.iter().take(40).enumerate() {
+            assert!(heap.decrease_key(handle, 100 + offset as u64));
+        }
+        let mut expected_ordering: Vec<u64> = (40..63).chain(100..140).collect();
+        expected_ordering.reverse();
+        for number in expected_ordering {
+            let actual = heap.top().unwrap();
+            assert_eq!(actual, number);
+        }
+        assert!(heap.is_empty());
+    }
+}
@@ -0,0 +1,30 @@
+use alloc::rc::Rc;
+use core::cell::RefCell;
+
+/// Identifies a node based heap so its handles can be told apart from another
+/// heap's. When a heap is melded into another its id forwards to the other's,
+/// so handles taken before the meld keep working.
+#[derive(Clone, Default)]
+pub(crate) struct HeapId(Rc<RefCell<Option<HeapId>>>);
+
+impl HeapId {
+    /// Makes `self` stand for `heap`, once `self`'s heap is melded into it.
+    pub(crate) fn forward_to(&self, heap: &HeapId) {
+        *self.0.borrow_mut() = Some(heap.clone());
+    }
+
+    /// Whether `self` stands for `heap`, following any melds.
+    pub(crate) fn is(&self, heap: &HeapId) -> bool {
+        let mut id = self.clone();
+        loop {
+            if Rc::ptr_eq(&id.0, &heap.0) {
+                return true;
+            }
+            let forward = id.0.borrow().clone();
+            match forward {
+                Some(forward) => id = forward,
+                None => return false,
+            }
+        }
+    }
+}
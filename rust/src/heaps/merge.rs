@@ -0,0 +1,262 @@
+use alloc::{vec, vec::Vec};
+use core::{cmp::Ordering, mem::replace};
+
+use super::d_heap::{heapify_by, push_down_by};
+
+struct HeadTail<I: Iterator> {
+    head: I::Item,
+    tail: I,
+}
+
+/// Iterator returned by [`kmerge`] and [`kmerge_by`].
+///
+/// Keeps the head of every input in a `B`-ary heap, sifted with the same
+/// routines as [`DHeap`](super::d_heap::DHeap), so each item costs one push
+/// down of O(B log_B k) comparisons.
+pub struct KMergeBy<I: Iterator, F, const B: usize> {
+    heap: Vec<HeadTail<I>>,
+    compare: F,
+}
+
+pub type KMerge<I, const B: usize> =
+    KMergeBy<I, fn(&<I as Iterator>::Item, &<I as Iterator>::Item) -> Ordering, B>;
+
+/// Merges sorted (ascending) iterators into one sorted iterator.
+pub fn kmerge<I, const B: usize>(iterables: I) -> KMerge<<I::Item as IntoIterator>::IntoIter, B>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    kmerge_by::<_, _, B>(iterables, Ord::cmp)
+}
+
+/// Merges iterators sorted according to `compare` into one sorted iterator.
+/// Of equal items, the one from the earlier input isn't guaranteed to come
+/// first.
+pub fn kmerge_by<I, F, const B: usize>(
+    iterables: I,
+    compare: F,
+) -> KMergeBy<<I::Item as IntoIterator>::IntoIter, F, B>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    F: FnMut(&<I::Item as IntoIterator>::Item, &<I::Item as IntoIterator>::Item) -> Ordering,
+{
+    let heap = iterables
+        .into_iter()
+        .filter_map(|iterable| {
+            let mut tail = iterable.into_iter();
+            tail.next().map(|head| HeadTail { head, tail })
+        })
+        .collect();
+    let mut merge = KMergeBy { heap, compare };
+    let compare = &mut merge.compare;
+    heapify_by::<_, _, B>(&mut merge.heap, &mut |a: &HeadTail<_>, b: &HeadTail<_>| {
+        compare(&b.head, &a.head) == Ordering::Less
+    });
+    merge
+}
+
+impl<I, F, const B: usize> Iterator for KMergeBy<I, F, B>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let root = self.heap.first_mut()?;
+        let item = match root.tail.next() {
+            Some(next) => replace(&mut root.head, next),
+            None => self.heap.swap_remove(0).head,
+        };
+        let compare = &mut self.compare;
+        // the smallest head has to end up on top of this max-heap
+        push_down_by::<_, _, B>(
+            &mut self.heap,
+            0,
+            &mut |a: &HeadTail<I>, b: &HeadTail<I>| compare(&b.head, &a.head) == Ordering::Less,
+        );
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.heap
+            .iter()
+            .fold((0, Some(0)), |(low, high), head_tail| {
+                let (tail_low, tail_high) = head_tail.tail.size_hint();
+                (
+                    low.saturating_add(tail_low).saturating_add(1),
+                    high.zip(tail_high)
+                        .and_then(|(high, tail_high)| high.checked_add(tail_high)?.checked_add(1)),
+                )
+            })
+    }
+}
+
+/// Iterator returned by [`kmerge_loser_tree`] and [`kmerge_loser_tree_by`].
+///
+/// A tournament tree over the inputs where each internal node remembers the
+/// loser of the match played there. Replacing the winner only replays the
+/// matches on its path to the root, exactly log2 k comparisons per item.
+pub struct LoserTree<I: Iterator, F> {
+    heads: Vec<Option<I::Item>>,
+    tails: Vec<I>,
+    // tree[0] is the overall winner, tree[1..] the losers of each match
+    tree: Vec<usize>,
+    compare: F,
+}
+
+pub type KMergeLoserTree<I> =
+    LoserTree<I, fn(&<I as Iterator>::Item, &<I as Iterator>::Item) -> Ordering>;
+
+/// Merges sorted (ascending) iterators using a loser tree.
+pub fn kmerge_loser_tree<I>(iterables: I) -> KMergeLoserTree<<I::Item as IntoIterator>::IntoIter>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    <I::Item as IntoIterator>::Item: Ord,
+{
+    kmerge_loser_tree_by(iterables, Ord::cmp)
+}
+
+/// Merges iterators sorted according to `compare` using a loser tree.
+pub fn kmerge_loser_tree_by<I, F>(
+    iterables: I,
+    compare: F,
+) -> LoserTree<<I::Item as IntoIterator>::IntoIter, F>
+where
+    I: IntoIterator,
+    I::Item: IntoIterator,
+    F: FnMut(&<I::Item as IntoIterator>::Item, &<I::Item as IntoIterator>::Item) -> Ordering,
+{
+    let mut tails: Vec<_> = iterables.into_iter().map(IntoIterator::into_iter).collect();
+    let heads = tails.iter_mut().map(Iterator::next).collect();
+    let size = tails.len();
+    let mut loser_tree = LoserTree {
+        heads,
+        tails,
+        tree: vec![0; size],
+        compare,
+    };
+    // play every match bottom up, leaves live at size..2 * size
+    let mut winners = vec![0; 2 * size];
+    for (leaf, winner) in winners[size..].iter_mut().enumerate() {
+        *winner = leaf;
+    }
+    for node in (1..size).rev() {
+        let (left, right) = (winners[2 * node], winners[2 * node + 1]);
+        if loser_tree.beats(left, right) {
+            winners[node] = left;
+            loser_tree.tree[node] = right;
+        } else {
+            winners[node] = right;
+            loser_tree.tree[node] = left;
+        }
+    }
+    if size > 1 {
+        loser_tree.tree[0] = winners[1];
+    }
+    loser_tree
+}
+
+impl<I, F> LoserTree<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    /// Whether input `a` comes before input `b`, exhausted inputs losing.
+    fn beats(&mut self, a: usize, b: usize) -> bool {
+        match (&self.heads[a], &self.heads[b]) {
+            (Some(a), Some(b)) => (self.compare)(b, a) != Ordering::Less,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+impl<I, F> Iterator for LoserTree<I, F>
+where
+    I: Iterator,
+    F: FnMut(&I::Item, &I::Item) -> Ordering,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<I::Item> {
+        let mut winner = *self.tree.first()?;
+        let item = self.heads[winner].take()?;
+        self.heads[winner] = self.tails[winner].next();
+        let mut node = (winner + self.tree.len()) / 2;
+        while node > 0 {
+            if self.beats(self.tree[node], winner) {
+                core::mem::swap(&mut self.tree[node], &mut winner);
+            }
+            node /= 2;
+        }
+        self.tree[0] = winner;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_inputs() -> Vec<Vec<u64>> {
+        vec![
+            vec![3, 8, 9],
+            vec![],
+            vec![5, 10],
+            vec![7, 8, 9, 11],
+            vec![1],
+        ]
+    }
+
+    #[test]
+    fn it_should_merge_sorted_inputs() {
+        let expected = vec![1, 3, 5, 7, 8, 8, 9, 9, 10, 11];
+        let merged: Vec<u64> = kmerge::<_, 3>(create_inputs()).collect();
+        assert_eq!(expected, merged);
+        assert_eq!((10, Some(10)), kmerge::<_, 2>(create_inputs()).size_hint());
+        let merged: Vec<u64> = kmerge_loser_tree(create_inputs()).collect();
+        assert_eq!(expected, merged);
+    }
+
+    #[test]
+    fn it_should_merge_with_a_custom_comparator() {
+        let inputs: Vec<Vec<u64>> = create_inputs()
+            .into_iter()
+            .map(|input| input.into_iter().rev().collect())
+            .collect();
+        let expected = vec![11, 10, 9, 9, 8, 8, 7, 5, 3, 1];
+        let merged: Vec<u64> = kmerge_by::<_, _, 4>(inputs.clone(), |a, b| b.cmp(a)).collect();
+        assert_eq!(expected, merged);
+        let merged: Vec<u64> = kmerge_loser_tree_by(inputs, |a: &u64, b: &u64| b.cmp(a)).collect();
+        assert_eq!(expected, merged);
+    }
+
+    #[test]
+    fn it_should_handle_no_inputs_and_a_single_input() {
+        let none: Vec<Vec<u64>> = vec![];
+        assert_eq!(None, kmerge::<_, 2>(none.clone()).next());
+        assert_eq!(None, kmerge_loser_tree(none).next());
+        let merged: Vec<u64> = kmerge_loser_tree(vec![vec![1, 2, 3]]).collect();
+        assert_eq!(vec![1, 2, 3], merged);
+    }
+
+    #[test]
+    fn it_should_agree_for_many_inputs() {
+        let inputs: Vec<Vec<u64>> = (0..257)
+            .map(|k| (0..k % 13).map(|n| (n * 31 + k) % 97).collect::<Vec<_>>())
+            .map(|mut input| {
+                input.sort();
+                input
+            })
+            .collect();
+        let mut expected: Vec<u64> = inputs.iter().flatten().copied().collect();
+        expected.sort();
+        assert_eq!(expected, kmerge::<_, 4>(inputs.clone()).collect::<Vec<_>>());
+        assert_eq!(expected, kmerge_loser_tree(inputs).collect::<Vec<_>>());
+    }
+}
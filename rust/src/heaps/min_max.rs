@@ -0,0 +1,298 @@
+use alloc::vec::Vec;
+
+/// Which end of a bounded [`MinMaxHeap`] gives way when it is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Evict {
+    Min,
+    Max,
+}
+
+/// A double-ended priority queue backed by a binary min-max heap.
+///
+/// Nodes on even levels are smaller than all of their descendants and nodes on
+/// odd levels are larger, so the minimum is the root and the maximum is one of
+/// its children. Both ends can be peeked in O(1) and popped in O(log n).
+#[derive(Debug)]
+pub struct MinMaxHeap<T: Ord> {
+    elements: Vec<T>,
+    bound: Option<(usize, Evict)>,
+}
+
+impl<T: Ord> MinMaxHeap<T> {
+    pub fn new() -> Self {
+        MinMaxHeap {
+            elements: Vec::new(),
+            bound: None,
+        }
+    }
+
+    /// Creates a heap holding at most `capacity` elements. Once full, each
+    /// insert removes and returns an element from the `evict` end.
+    pub fn bounded(capacity: usize, evict: Evict) -> Self {
+        MinMaxHeap {
+            elements: Vec::with_capacity(capacity),
+            bound: Some((capacity, evict)),
+        }
+    }
+
+    /// Inserts `element`, returning the evicted element when a bounded heap is
+    /// full. That may be `element` itself if it would have been evicted
+    /// straight away.
+    pub fn insert(&mut self, element: T) -> Option<T> {
+        if let Some((capacity, evict)) = self.bound {
+            if self.elements.len() >= capacity {
+                return self.insert_evicting(element, evict);
+            }
+        }
+        self.elements.push(element);
+        self.bubble_up(self.elements.len() - 1);
+        None
+    }
+
+    fn insert_evicting(&mut self, element: T, evict: Evict) -> Option<T> {
+        let index = match (evict, self.max_index()) {
+            (_, None) => return Some(element),
+            (Evict::Min, Some(_)) if element <= self.elements[0] => return Some(element),
+            (Evict::Min, Some(_)) => 0,
+            (Evict::Max, Some(max)) if element >= self.elements[max] => return Some(element),
+            (Evict::Max, Some(max)) => max,
+        };
+        let evicted = self.remove_at(index);
+        self.elements.push(element);
+        self.bubble_up(self.elements.len() - 1);
+        Some(evicted)
+    }
+
+    pub fn peek_min(&self) -> Option<&T> {
+        self.elements.first()
+    }
+
+    pub fn peek_max(&self) -> Option<&T> {
+        self.max_index().map(|index| &self.elements[index])
+    }
+
+    pub fn pop_min(&mut self) -> Option<T> {
+        if self.elements.is_empty() {
+            return None;
+        }
+        Some(self.remove_at(0))
+    }
+
+    pub fn pop_max(&mut self) -> Option<T> {
+        self.max_index().map(|index| self.remove_at(index))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    pub fn size(&self) -> usize {
+        self.elements.len()
+    }
+
+    fn max_index(&self) -> Option<usize> {
+        match self.elements.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ if self.elements[2] > self.elements[1] => Some(2),
+            _ => Some(1),
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> T {
+        let removed = self.elements.swap_remove(index);
+        if index < self.elements.len() {
+            self.trickle_down(index);
+        }
+        removed
+    }
+
+    fn bubble_up(&mut self, index: usize) {
+        if index == 0 {
+            return;
+        }
+        let parent_index = get_parent_index(index);
+        if is_min_level(index) {
+            if self.elements[index] > self.elements[parent_index] {
+                self.elements.swap(index, parent_index);
+                self.bubble_up_level(parent_index, |a, b| a > b);
+            } else {
+                self.bubble_up_level(index, |a, b| a < b);
+            }
+        } else if self.elements[index] < self.elements[parent_index] {
+            self.elements.swap(index, parent_index);
+            self.bubble_up_level(parent_index, |a, b| a < b);
+        } else {
+            self.bubble_up_level(index, |a, b| a > b);
+        }
+    }
+
+    /// Bubble up through grandparents, which share the node's min or max level.
+    fn bubble_up_level(&mut self, mut index: usize, outranks: fn(&T, &T) -> bool) {
+        while index > 2 {
+            let grandparent_index = get_parent_index(get_parent_index(index));
+            if outranks(&self.elements[index], &self.elements[grandparent_index]) {
+                self.elements.swap(index, grandparent_index);
+                index = grandparent_index;
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn trickle_down(&mut self, index: usize) {
+        if is_min_level(index) {
+            self.trickle_down_level(index, |a, b| a < b);
+        } else {
+            self.trickle_down_level(index, |a, b| a > b);
+        }
+    }
+
+    fn trickle_down_level(&mut self, mut index: usize, outranks: fn(&T, &T) -> bool) {
+        let size = self.elements.len();
+        loop {
+            let first_child_index = get_first_child_index(index);
+            if first_child_index >= size {
+                break;
+            }
+            // best of the children and grandchildren
+            let first_grandchild_index = get_first_child_index(first_child_index);
+            let mut best_index = first_child_index;
+            let candidates = (first_child_index + 1..first_child_index + 2)
+                .chain(first_grandchild_index..first_grandchild_index + 4);
+            for candidate_index in candidates.filter(|&i| i < size) {
+                if outranks(&self.elements[candidate_index], &self.elements[best_index]) {
+                    best_index = candidate_index;
+                }
+            }
+            if !outranks(&self.elements[best_index], &self.elements[index]) {
+                break;
+            }
+            self.elements.swap(best_index, index);
+            if best_index < first_grandchild_index {
+                break;
+            }
+            let parent_index = get_parent_index(best_index);
+            if outranks(&self.elements[parent_index], &self.elements[best_index]) {
+                self.elements.swap(parent_index, best_index);
+            }
+            index = best_index;
+        }
+    }
+}
+
+impl<T: Ord> Default for MinMaxHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_min_level(index: usize) -> bool {
+    (index + 1).ilog2() & 1 == 0
+}
+
+fn get_parent_index(index: usize) -> usize {
+    (index - 1) / 2
+}
+
+fn get_first_child_index(index: usize) -> usize {
+    2 * index + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use crate::heaps::test_support::{self, priority_queue_tests, TestHeap};
+
+    impl TestHeap for MinMaxHeap<u64> {
+        fn insert(&mut self, element: u64) {
+            MinMaxHeap::insert(self, element);
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            self.pop_max()
+        }
+
+        fn peek(&self) -> Option<u64> {
+            self.peek_max().copied()
+        }
+
+        fn size(&self) -> usize {
+            MinMaxHeap::size(self)
+        }
+    }
+
+    fn create_heap() -> MinMaxHeap<u64> {
+        test_support::create_heap()
+    }
+
+    priority_queue_tests!(MinMaxHeap<u64>);
+
+    #[test]
+    fn it_should_pop_from_both_ends() {
+        let mut heap = create_heap();
+        assert_eq!(Some(&3), heap.peek_min());
+        assert_eq!(Some(&10), heap.peek_max());
+        assert_eq!(Some(3), heap.pop_min());
+        assert_eq!(Some(10), heap.pop_max());
+        assert_eq!(Some(9), heap.pop_max());
+        assert_eq!(Some(5), heap.pop_min());
+        assert_eq!(Some(7), heap.pop_min());
+        assert_eq!(Some(9), heap.pop_max());
+        assert_eq!(Some(8), heap.pop_max());
+        assert_eq!(Some(8), heap.pop_min());
+        assert_eq!(None, heap.pop_min());
+        assert_eq!(None, heap.pop_max());
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_match_a_sorted_model_for_random_operations() {
+        let mut rng = StdRng::seed_from_u64(30);
+        let mut heap = MinMaxHeap::new();
+        let mut model: Vec<u32> = Vec::new();
+        for _ in 0..5_000 {
+            match rng.gen_range(0..4) {
+                0 => {
+                    let expected = (!model.is_empty()).then(|| model.remove(0));
+                    assert_eq!(expected, heap.pop_min());
+                }
+                1 => assert_eq!(model.pop(), heap.pop_max()),
+                _ => {
+                    let value = rng.gen_range(0..100);
+                    heap.insert(value);
+                    let position = model.partition_point(|&e| e < value);
+                    model.insert(position, value);
+                }
+            }
+            assert_eq!(model.first(), heap.peek_min());
+            assert_eq!(model.last(), heap.peek_max());
+            assert_eq!(model.len(), heap.size());
+        }
+    }
+
+    #[test]
+    fn it_should_evict_from_the_configured_end_when_bounded() {
+        let mut heap = MinMaxHeap::bounded(3, Evict::Min);
+        assert_eq!(None, heap.insert(5));
+        assert_eq!(None, heap.insert(1));
+        assert_eq!(None, heap.insert(9));
+        assert_eq!(Some(1), heap.insert(7));
+        assert_eq!(Some(2), heap.insert(2));
+        assert_eq!(Some(&5), heap.peek_min());
+        assert_eq!(3, heap.size());
+
+        let mut heap = MinMaxHeap::bounded(3, Evict::Max);
+        for number in [5, 1, 9] {
+            heap.insert(number);
+        }
+        assert_eq!(Some(9), heap.insert(7));
+        assert_eq!(Some(8), heap.insert(8));
+        assert_eq!(Some(7), heap.insert(0));
+        assert_eq!(Some(&5), heap.peek_max());
+        assert_eq!(Some(&0), heap.peek_min());
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    cell::Cell,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{Mutex, MutexGuard, TryLockError},
+};
+
+use super::d_heap::DHeap;
+
+/// A relaxed concurrent priority queue made of many independently locked
+/// [`DHeap`]s.
+///
+/// With `p` threads and a factor `c` it holds `c * p` heaps. Inserts go to a
+/// random heap and pops take the better top of two random heaps, so threads
+/// rarely contend for the same lock. In exchange, `pop` may return an element
+/// that is not the global top; the expected rank error grows with `c * p`
+/// (see [`measure_rank_error`]).
+pub struct MultiQueue<T: Ord, const B: usize = 4> {
+    queues: Vec<Mutex<DHeap<T, B>>>,
+}
+
+impl<T: Ord, const B: usize> MultiQueue<T, B> {
+    /// Creates a queue tuned for `threads` threads with `c` heaps per thread.
+    pub fn new(threads: usize, c: usize) -> Self {
+        let count = std::cmp::max(threads * c, 2);
+        MultiQueue {
+            queues: (0..count).map(|_| Mutex::new(DHeap::new(vec![]))).collect(),
+        }
+    }
+
+    pub fn insert(&self, element: T) {
+        loop {
+            if let Some(mut queue) = try_lock(&self.queues[self.random_index()]) {
+                queue.insert(element);
+                return;
+            }
+        }
+    }
+
+    /// Removes the higher of the tops of two random heaps, falling back to a
+    /// scan of every heap when both are empty. Returns `None` only when every
+    /// heap was seen empty.
+    pub fn pop(&self) -> Option<T> {
+        loop {
+            let first = self.random_index();
+            let mut second = self.random_index();
+            if second == first {
+                second = (first + 1) % self.queues.len();
+            }
+            // pick another pair rather than wait on a busy heap
+            let (Some(mut first_queue), Some(mut second_queue)) = (
+                try_lock(&self.queues[first]),
+                try_lock(&self.queues[second]),
+            ) else {
+                continue;
+            };
+            let popped = match (first_queue.peek(), second_queue.peek()) {
+                (Some(first_top), Some(second_top)) if second_top > first_top => second_queue.top(),
+                (Some(_), _) => first_queue.top(),
+                (None, _) => second_queue.top(),
+            };
+            drop(second_queue);
+            drop(first_queue);
+            return popped.or_else(|| self.queues.iter().find_map(|queue| lock(queue).top()));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queues.iter().all(|queue| lock(queue).is_empty())
+    }
+
+    pub fn size(&self) -> usize {
+        self.queues.iter().map(|queue| lock(queue).size()).sum()
+    }
+
+    pub fn queue_count(&self) -> usize {
+        self.queues.len()
+    }
+
+    fn random_index(&self) -> usize {
+        thread_local! {
+            static STATE: Cell<u64> = Cell::new({
+                let mut hasher = RandomState::new().build_hasher();
+                hasher.write_u64(0);
+                hasher.finish() | 1
+            });
+        }
+        STATE.with(|state| {
+            // xorshift64
+            let mut x = state.get();
+            x ^= x << 13;
+            x ^= x >> 7;
+            x ^= x << 17;
+            state.set(x);
+            (x % self.queues.len() as u64) as usize
+        })
+    }
+}
+
+fn lock<T: Ord, const B: usize>(queue: &Mutex<DHeap<T, B>>) -> MutexGuard<'_, DHeap<T, B>> {
+    queue.lock().expect("MultiQueue lock poisoned")
+}
+
+/// Like [`lock`], but returns `None` instead of waiting when `queue` is busy.
+fn try_lock<T: Ord, const B: usize>(
+    queue: &Mutex<DHeap<T, B>>,
+) -> Option<MutexGuard<'_, DHeap<T, B>>> {
+    match queue.try_lock() {
+        Ok(queue) => Some(queue),
+        Err(TryLockError::WouldBlock) => None,
+        // a poisoned heap never frees up, so retrying would spin forever
+        Err(TryLockError::Poisoned(_)) => panic!("MultiQueue lock poisoned"),
+    }
+}
+
+/// How far a sequence of relaxed pops strayed from exact priority order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RankErrorStats {
+    pub pops: usize,
+    pub mean: f64,
+    pub max: usize,
+}
+
+/// Pops every element of `elements` back out of `queue` and measures the rank
+/// error of each pop: how many elements still queued ranked above it. An exact
+/// priority queue scores 0 throughout.
+///
+/// `queue` should start empty. Pops are made from the calling thread so the
+/// result reflects the relaxation of the data structure itself.
+pub fn measure_rank_error<T: Ord + Clone, const B: usize>(
+    queue: &MultiQueue<T, B>,
+    elements: Vec<T>,
+) -> RankErrorStats {
+    let mut distinct = elements.clone();
+    distinct.sort();
+    distinct.dedup();
+    let mut queued = QueuedCounts::new(distinct.len());
+    for element in elements {
+        queued.add(distinct.binary_search(&element).unwrap(), 1);
+        queue.insert(element);
+    }
+    let mut stats = RankErrorStats {
+        pops: 0,
+        mean: 0.0,
+        max: 0,
+    };
+    let mut total_error = 0;
+    while let Some(element) = queue.pop() {
+        let position = distinct.binary_search(&element).unwrap();
+        let rank_error = queued.total() - queued.up_to(position);
+        queued.add(position, -1);
+        total_error += rank_error;
+        stats.max = stats.max.max(rank_error);
+        stats.pops += 1;
+    }
+    if stats.pops > 0 {
+        stats.mean = total_error as f64 / stats.pops as f64;
+    }
+    stats
+}
+
+/// Fenwick tree counting how many copies of each distinct element are queued.
+struct QueuedCounts {
+    tree: Vec<isize>,
+}
+
+impl QueuedCounts {
+    fn new(size: usize) -> Self {
+        QueuedCounts {
+            tree: vec![0; size + 1],
+        }
+    }
+
+    fn add(&mut self, position: usize, delta: isize) {
+        let mut index = position + 1;
+        while index < self.tree.len() {
+            self.tree[index] += delta;
+            index += index & index.wrapping_neg();
+        }
+    }
+
+    /// Number of queued elements at or below `position`.
+    fn up_to(&self, position: usize) -> usize {
+        let mut index = position + 1;
+        let mut total = 0;
+        while index > 0 {
+            total += self.tree[index];
+            index -= index & index.wrapping_neg();
+        }
+        total as usize
+    }
+
+    fn total(&self) -> usize {
+        self.up_to(self.tree.len() - 2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        panic::{self, AssertUnwindSafe},
+        sync::Arc,
+        thread,
+    };
+
+    #[test]
+    fn it_should_return_every_element() {
+        let queue: MultiQueue<u64, 3> = MultiQueue::new(2, 2);
+        for number in [9, 10, 9, 8, 7, 5, 3, 8] {
+            queue.insert(number);
+        }
+        assert_eq!(4, queue.queue_count());
+        assert_eq!(8, queue.size());
+        let mut popped: Vec<u64> = std::iter::from_fn(|| queue.pop()).collect();
+        popped.sort();
+        assert_eq!(vec![3, 5, 7, 8, 8, 9, 9, 10], popped);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn it_should_measure_a_bounded_rank_error() {
+        let exact: MultiQueue<u64> = MultiQueue::new(1, 1);
+        assert_eq!(
+            RankErrorStats {
+                pops: 3,
+                mean: 0.0,
+                max: 0
+            },
+            measure_rank_error(&exact, vec![1, 3, 2])
+        );
+        let relaxed: MultiQueue<u64> = MultiQueue::new(4, 2);
+        let stats = measure_rank_error(&relaxed, (0..10_000).collect());
+        assert_eq!(10_000, stats.pops);
+        assert!(stats.mean < 100.0, "{stats:?}");
+    }
+
+    #[test]
+    fn it_should_deliver_every_element_once_under_contention() {
+        let queue: Arc<MultiQueue<u64>> = Arc::new(MultiQueue::new(4, 2));
+        let handles: Vec<_> = (0..4u64)
+            .map(|thread_index| {
+                let queue = queue.clone();
+                thread::spawn(move || {
+                    let mut popped = Vec::new();
+                    for n in 0..5_000 {
+                        queue.insert(thread_index * 5_000 + n);
+                        if n % 2 == 0 {
+                            popped.extend(queue.pop());
+                        }
+                    }
+                    popped
+                })
+            })
+            .collect();
+        let mut received: Vec<u64> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect();
+        received.extend(std::iter::from_fn(|| queue.pop()));
+        received.sort();
+        assert_eq!((0..20_000).collect::<Vec<_>>(), received);
+    }
+
+    #[test]
+    fn it_should_panic_instead_of_spinning_on_a_poisoned_heap() {
+        let queue: MultiQueue<u64> = MultiQueue::new(1, 2);
+        let poisoning = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _queue = lock(&queue.queues[0]);
+            panic!("poison the first heap");
+        }));
+        assert!(poisoning.is_err());
+        // with two heaps every pop tries both, and inserts soon pick the
+        // poisoned one
+        assert!(panic::catch_unwind(AssertUnwindSafe(|| queue.pop())).is_err());
+        let inserting = panic::catch_unwind(AssertUnwindSafe(|| {
+            for number in 0..100 {
+                queue.insert(number);
+            }
+        }));
+        assert!(inserting.is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use alloc::{
+    rc::{Rc, Weak},
+    vec::Vec,
+};
+use core::cell::RefCell;
+
+use super::heap_id::HeapId;
+
+type NodeLink<T> = Rc<RefCell<PairingNode<T>>>;
+
+struct PairingNode<T> {
+    element: T,
+    child: Option<NodeLink<T>>,
+    next: Option<NodeLink<T>>,
+    // previous sibling, or the parent for a first child
+    prev: Option<Weak<RefCell<PairingNode<T>>>>,
+}
+
+/// A reference to an element in a [`PairingHeap`], used for `decrease_key`.
+///
+/// Handles don't keep their element alive, so a handle to an element that has
+/// since been popped is simply stale.
+pub struct Handle<T>(Weak<RefCell<PairingNode<T>>>, HeapId);
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle(self.0.clone(), self.1.clone())
+    }
+}
+
+/// A max-ordered pairing heap with O(1) `insert` and `meld`, o(log n)
+/// amortized `decrease_key` and O(log n) amortized `top`.
+pub struct PairingHeap<T: Ord> {
+    root: Option<NodeLink<T>>,
+    size: usize,
+    id: HeapId,
+}
+
+impl<T: Ord> PairingHeap<T> {
+    pub fn new() -> Self {
+        PairingHeap {
+            root: None,
+            size: 0,
+            id: HeapId::default(),
+        }
+    }
+
+    pub fn insert(&mut self, element: T) -> Handle<T> {
+        let node = Rc::new(RefCell::new(PairingNode {
+            element,
+            child: None,
+            next: None,
+            prev: None,
+        }));
+        let handle = Handle(Rc::downgrade(&node), self.id.clone());
+        self.root = Some(match self.root.take() {
+            Some(root) => link(root, node),
+            None => node,
+        });
+        self.size += 1;
+        handle
+    }
+
+    pub fn top(&mut self) -> Option<T> {
+        let root = self.root.take()?;
+        let mut children = Vec::new();
+        let mut next_child = root.borrow_mut().child.take();
+        while let Some(child) = next_child {
+            next_child = child.borrow_mut().next.take();
+            child.borrow_mut().prev = None;
+            children.push(child);
+        }
+        self.root = merge_pairs(children);
+        self.size -= 1;
+        let node = Rc::try_unwrap(root)
+            .ok()
+            .expect("Heap nodes should only be owned by the heap");
+        Some(node.into_inner().element)
+    }
+
+    pub fn peek(&self) -> Option<core::cell::Ref<'_, T>> {
+        self.root
+            .as_ref()
+            .map(|root| core::cell::Ref::map(root.borrow(), |node| &node.element))
+    }
+
+    /// Moves all elements of `other` into this heap in O(1).
+    pub fn meld(&mut self, mut other: PairingHeap<T>) {
+        self.root = match (self.root.take(), other.root.take()) {
+            (Some(root), Some(other_root)) => Some(link(root, other_root)),
+            (root, other_root) => root.or(other_root),
+        };
+        self.size += other.size;
+        other.id.forward_to(&self.id);
+    }
+
+    /// Replaces the element behind `handle` with `element`, which must rank at
+    /// least as high, moving it towards the top. With `Reverse` priorities this
+    /// is a literal decrease of the key.
+    ///
+    /// Returns false, leaving the heap untouched, if the handle is stale or
+    /// belongs to another heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `element` ranks below the current element.
+    pub fn decrease_key(&mut self, handle: &Handle<T>, element: T) -> bool {
+        if !handle.1.is(&self.id) {
+            return false;
+        }
+        let Some(node) = handle.0.upgrade() else {
+            return false;
+        };
+        assert!(
+            element >= node.borrow().element,
+            "decrease_key must not move an element away from the top"
+        );
+        node.borrow_mut().element = element;
+        let root = self.root.take().expect("a live handle implies a root");
+        if Rc::ptr_eq(&root, &node) {
+            self.root = Some(root);
+        } else {
+            cut(&node);
+            self.root = Some(link(root, node));
+        }
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T: Ord> Default for PairingHeap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> Drop for PairingHeap<T> {
+    fn drop(&mut self) {
+        // unlink iteratively, long sibling chains would otherwise overflow the
+        // stack through recursive Rc drops
+        let mut pending: Vec<NodeLink<T>> = self.root.take().into_iter().collect();
+        while let Some(node) = pending.pop() {
+            let mut node = node.borrow_mut();
+            pending.extend(node.child.take());
+            pending.extend(node.next.take());
+        }
+    }
+}
+
+/// Make the lower ranked of two roots the first child of the other.
+fn link<T: Ord>(a: NodeLink<T>, b: NodeLink<T>) -> NodeLink<T> {
+    let (parent, child) = if b.borrow().element > a.borrow().element {
+        (b, a)
+    } else {
+        (a, b)
+    };
+    {
+        let mut parent_node = parent.borrow_mut();
+        let mut child_node = child.borrow_mut();
+        if let Some(first_child) = parent_node.child.take() {
+            first_child.borrow_mut().prev = Some(Rc::downgrade(&child));
+            child_node.next = Some(first_child);
+        }
+        child_node.prev = Some(Rc::downgrade(&parent));
+    }
+    parent.borrow_mut().child = Some(child);
+    parent
+}
+
+/// Two pass pairing: link neighbours left to right, then fold right to left.
+fn merge_pairs<T: Ord>(children: Vec<NodeLink<T>>) -> Option<NodeLink<T>> {
+    let mut paired = Vec::with_capacity(children.len() / 2 + 1);
+    let mut children = children.into_iter();
+    while let Some(first) = children.next() {
+        paired.push(match children.next() {
+            Some(second) => link(first, second),
+            None => first,
+        });
+    }
+    paired.into_iter().rev().reduce(|acc, node| link(node, acc))
+}
+
+/// Detach `node` (and its subtree) from its parent or previous sibling.
+fn cut<T>(node: &NodeLink<T>) {
+    let mut node_ref = node.borrow_mut();
+    let prev = node_ref
+        .prev
+        .take()
+        .and_then(|prev| prev.upgrade())
+        .expect("non-root nodes always have a prev link");
+    let next = node_ref.next.take();
+    if let Some(next) = &next {
+        next.borrow_mut().prev = Some(Rc::downgrade(&prev));
+    }
+    let mut prev_ref = prev.borrow_mut();
+    let is_first_child = prev_ref
+        .child
+        .as_ref()
+        .is_some_and(|child| Rc::ptr_eq(child, node));
+    if is_first_child {
+        prev_ref.child = next;
+    } else {
+        prev_ref.next = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::heaps::test_support::{self, priority_queue_tests, TestHeap};
+
+    impl TestHeap for PairingHeap<u64> {
+        fn insert(&mut self, element: u64) {
+            PairingHeap::insert(self, element);
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            PairingHeap::top(self)
+        }
+
+        fn peek(&self) -> Option<u64> {
+            PairingHeap::peek(self).map(|top| *top)
+        }
+
+        fn size(&self) -> usize {
+            PairingHeap::size(self)
+        }
+    }
+
+    fn create_heap() -> PairingHeap<u64> {
+        test_support::create_heap()
+    }
+
+    priority_queue_tests!(PairingHeap<u64>);
+
+    #[test]
+    fn it_should_be_able_to_decrease_keys() {
+        let expected_ordering = vec![14, 12, 9, 9, 8, 8, 7, 5, 3];
+        let mut heap = create_heap();
+        let handle = heap.insert(4);
+        let nested = heap.insert(2);
+        heap.top();
+        assert!(heap.decrease_key(&handle, 14));
+        assert!(heap.decrease_key(&nested, 12));
+        assert_eq!(14, *heap.peek().unwrap());
+        for number in expected_ordering {
+            let actual = heap.top().unwrap();
+            assert_eq!(actual, number);
+        }
+        assert!(!heap.decrease_key(&handle, 20));
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_be_able_to_meld_heaps() {
+        let expected_ordering = vec![12, 10, 9, 9, 8, 8, 7, 6, 5, 3];
+        let mut heap = create_heap();
+        let mut other = PairingHeap::new();
+        other.insert(6);
+        let handle = other.insert(1);
+        heap.meld(other);
+        assert!(heap.decrease_key(&handle, 12));
+        assert_eq!(10, heap.size());
+        for number in expected_ordering {
+            let actual = heap.top().unwrap();
+            assert_eq!(actual, number);
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_ignore_handles_from_other_heaps() {
+        let mut heap = PairingHeap::new();
+        let mut other = create_heap();
+        let handle = other.insert(1);
+        assert!(!heap.decrease_key(&handle, 12));
+        heap.insert(4);
+        assert!(!heap.decrease_key(&handle, 12));
+        assert_eq!(Some(4), heap.top());
+        assert!(heap.is_empty());
+        assert_eq!(9, other.size());
+        assert_eq!(Some(10), other.top());
+    }
+
+    #[test]
+    fn it_should_drop_large_heaps_without_overflowing() {
+        let mut heap = PairingHeap::new();
+        for number in 0..200_000 {
+            heap.insert(number);
+        }
+        drop(heap);
+    }
+}
@@ -0,0 +1,260 @@
+use alloc::{rc::Rc, sync::Arc, vec::Vec};
+use core::ops::Deref;
+
+/// The reference counted pointer a [`PersistentHeap`] shares its nodes with.
+pub trait SharedPointerKind {
+    type Pointer<T>: Deref<Target = T> + Clone;
+
+    fn new<T>(value: T) -> Self::Pointer<T>;
+
+    fn try_unwrap<T>(pointer: Self::Pointer<T>) -> Result<T, Self::Pointer<T>>;
+}
+
+/// Single threaded nodes shared through [`Rc`].
+pub struct RcPointer;
+
+/// Thread-safe nodes shared through [`Arc`].
+pub struct ArcPointer;
+
+impl SharedPointerKind for RcPointer {
+    type Pointer<T> = Rc<T>;
+
+    fn new<T>(value: T) -> Rc<T> {
+        Rc::new(value)
+    }
+
+    fn try_unwrap<T>(pointer: Rc<T>) -> Result<T, Rc<T>> {
+        Rc::try_unwrap(pointer)
+    }
+}
+
+impl SharedPointerKind for ArcPointer {
+    type Pointer<T> = Arc<T>;
+
+    fn new<T>(value: T) -> Arc<T> {
+        Arc::new(value)
+    }
+
+    fn try_unwrap<T>(pointer: Arc<T>) -> Result<T, Arc<T>> {
+        Arc::try_unwrap(pointer)
+    }
+}
+
+type NodeLink<T, P> = <P as SharedPointerKind>::Pointer<LeftistNode<T, P>>;
+
+struct LeftistNode<T, P: SharedPointerKind> {
+    element: T,
+    // length of the shortest path to a missing child
+    rank: usize,
+    left: Option<NodeLink<T, P>>,
+    right: Option<NodeLink<T, P>>,
+}
+
+/// An immutable max-ordered leftist heap.
+///
+/// `insert`, `pop` and `merge` return a new version of the heap and leave
+/// `self` untouched. Versions share every node off the O(log n) right spine
+/// that an operation rebuilds, so keeping old versions around and cloning a
+/// heap are both cheap. Rebuilt nodes clone their element, so large elements
+/// are best wrapped in `Rc`/`Arc` themselves.
+pub struct PersistentHeap<T, P: SharedPointerKind = RcPointer> {
+    root: Option<NodeLink<T, P>>,
+    size: usize,
+}
+
+/// A [`PersistentHeap`] whose versions can be shared between threads.
+pub type ArcPersistentHeap<T> = PersistentHeap<T, ArcPointer>;
+
+impl<T: Ord + Clone, P: SharedPointerKind> PersistentHeap<T, P> {
+    pub fn new() -> Self {
+        PersistentHeap {
+            root: None,
+            size: 0,
+        }
+    }
+
+    pub fn insert(&self, element: T) -> Self {
+        let node = P::new(LeftistNode {
+            element,
+            rank: 1,
+            left: None,
+            right: None,
+        });
+        PersistentHeap {
+            root: merge::<T, P>(self.root.clone(), Some(node)),
+            size: self.size + 1,
+        }
+    }
+
+    /// Returns the version of the heap without its top element.
+    pub fn pop(&self) -> Option<Self> {
+        self.root.as_ref().map(|root| PersistentHeap {
+            root: merge::<T, P>(root.left.clone(), root.right.clone()),
+            size: self.size - 1,
+        })
+    }
+
+    /// Returns a copy of the top element along with the version of the heap
+    /// without it.
+    pub fn top(&self) -> Option<(T, Self)> {
+        let element = self.peek()?.clone();
+        self.pop().map(|heap| (element, heap))
+    }
+
+    pub fn peek(&self) -> Option<&T> {
+        self.root.as_ref().map(|root| &root.element)
+    }
+
+    /// Returns a version holding the elements of both heaps.
+    pub fn merge(&self, other: &Self) -> Self {
+        PersistentHeap {
+            root: merge::<T, P>(self.root.clone(), other.root.clone()),
+            size: self.size + other.size,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl<T, P: SharedPointerKind> Clone for PersistentHeap<T, P> {
+    fn clone(&self) -> Self {
+        PersistentHeap {
+            root: self.root.clone(),
+            size: self.size,
+        }
+    }
+}
+
+impl<T: Ord + Clone, P: SharedPointerKind> Default for PersistentHeap<T, P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, P: SharedPointerKind> Drop for PersistentHeap<T, P> {
+    fn drop(&mut self) {
+        // free uniquely owned nodes iteratively, left spines can be long
+        // enough to overflow the stack through recursive drops
+        let mut pending: Vec<NodeLink<T, P>> = self.root.take().into_iter().collect();
+        while let Some(node) = pending.pop() {
+            if let Ok(mut node) = P::try_unwrap(node) {
+                pending.extend(node.left.take());
+                pending.extend(node.right.take());
+            }
+        }
+    }
+}
+
+fn rank<T, P: SharedPointerKind>(node: &Option<NodeLink<T, P>>) -> usize {
+    node.as_ref().map_or(0, |node| node.rank)
+}
+
+/// Merge along the right spines, copying only the nodes on that path.
+fn merge<T: Ord + Clone, P: SharedPointerKind>(
+    a: Option<NodeLink<T, P>>,
+    b: Option<NodeLink<T, P>>,
+) -> Option<NodeLink<T, P>> {
+    let (a, b) = match (a, b) {
+        (None, node) | (node, None) => return node,
+        (Some(a), Some(b)) if b.element > a.element => (b, a),
+        (Some(a), Some(b)) => (a, b),
+    };
+    let merged = merge::<T, P>(a.right.clone(), Some(b));
+    let (left, right) = if rank::<T, P>(&a.left) >= rank::<T, P>(&merged) {
+        (a.left.clone(), merged)
+    } else {
+        (merged, a.left.clone())
+    };
+    Some(P::new(LeftistNode {
+        element: a.element.clone(),
+        rank: rank::<T, P>(&right) + 1,
+        left,
+        right,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::heaps::test_support::{self, priority_queue_tests, TestHeap};
+
+    impl TestHeap for PersistentHeap<u64> {
+        fn insert(&mut self, element: u64) {
+            *self = PersistentHeap::insert(self, element);
+        }
+
+        fn top(&mut self) -> Option<u64> {
+            let (top, rest) = PersistentHeap::top(self)?;
+            *self = rest;
+            Some(top)
+        }
+
+        fn peek(&self) -> Option<u64> {
+            PersistentHeap::peek(self).copied()
+        }
+
+        fn size(&self) -> usize {
+            PersistentHeap::size(self)
+        }
+    }
+
+    fn create_heap() -> PersistentHeap<u64> {
+        test_support::create_heap()
+    }
+
+    fn drain<P: SharedPointerKind>(heap: &PersistentHeap<u64, P>) -> Vec<u64> {
+        let mut heap = heap.clone();
+        let mut ordering = Vec::new();
+        while let Some((number, rest)) = heap.top() {
+            ordering.push(number);
+            heap = rest;
+        }
+        ordering
+    }
+
+    priority_queue_tests!(PersistentHeap<u64>);
+
+    #[test]
+    fn it_should_leave_earlier_versions_untouched() {
+        let heap = create_heap();
+        let inserted = heap.insert(13);
+        let popped = heap.pop().unwrap();
+        let merged = popped.merge(&inserted);
+        assert_eq!(vec![10, 9, 9, 8, 8, 7, 5, 3], drain(&heap));
+        assert_eq!(vec![13, 10, 9, 9, 8, 8, 7, 5, 3], drain(&inserted));
+        assert_eq!(vec![9, 9, 8, 8, 7, 5, 3], drain(&popped));
+        assert_eq!(16, merged.size());
+        assert_eq!(Some(&13), merged.peek());
+        assert!(PersistentHeap::<u64>::new().pop().is_none());
+    }
+
+    #[test]
+    fn it_should_share_arc_versions_between_threads() {
+        let heap: ArcPersistentHeap<u64> =
+            (0..1_000).fold(PersistentHeap::new(), |h, n| h.insert(n));
+        let handles: Vec<_> = (0..4)
+            .map(|offset| {
+                let heap = heap.clone();
+                std::thread::spawn(move || heap.insert(2_000 + offset).pop().unwrap().size())
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(1_000, handle.join().unwrap());
+        }
+        assert_eq!(Some(&999), heap.peek());
+    }
+
+    #[test]
+    fn it_should_drop_long_chains_without_overflowing() {
+        let heap = (0..200_000u64).fold(PersistentHeap::<u64>::new(), |h, n| h.insert(n));
+        assert_eq!(Some(&199_999), heap.peek());
+        drop(heap);
+    }
+}
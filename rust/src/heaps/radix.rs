@@ -0,0 +1,159 @@
+use alloc::vec::Vec;
+
+/// Unsigned integer keys usable in a [`RadixHeap`].
+pub trait RadixKey: Copy + Ord {
+    const BITS: u32;
+
+    /// Number of significant bits in which `self` and `other` differ.
+    fn radix_distance(self, other: Self) -> u32;
+}
+
+macro_rules! impl_radix_key {
+    ($($t:ty),*) => {
+        $(
+            impl RadixKey for $t {
+                const BITS: u32 = <$t>::BITS;
+
+                #[inline]
+                fn radix_distance(self, other: Self) -> u32 {
+                    Self::BITS - (self ^ other).leading_zeros()
+                }
+            }
+        )*
+    };
+}
+
+impl_radix_key!(u8, u16, u32, u64, u128, usize);
+
+/// A min-ordered monotone priority queue for unsigned integer keys.
+///
+/// Items are kept in buckets by the highest bit in which their key differs
+/// from the last popped key. Popping only redistributes a bucket when bucket 0
+/// runs dry, giving O(log C) amortized operations where C is the key range.
+/// Keys must never be smaller than the last popped key, which holds for e.g.
+/// Dijkstra over non-negative edge weights.
+pub struct RadixHeap<K: RadixKey, V> {
+    buckets: Vec<Vec<(K, V)>>,
+    last: Option<K>,
+    size: usize,
+}
+
+impl<K: RadixKey, V> RadixHeap<K, V> {
+    pub fn new() -> Self {
+        RadixHeap {
+            buckets: (0..=K::BITS).map(|_| Vec::new()).collect(),
+            last: None,
+            size: 0,
+        }
+    }
+
+    /// Pushes `value` with priority `key`.
+    ///
+    /// In debug builds this panics if `key` is smaller than the last popped
+    /// key. In release builds such an item is still stored but may come out
+    /// of order.
+    pub fn push(&mut self, key: K, value: V) {
+        debug_assert!(
+            self.last.is_none_or(|last| key >= last),
+            "RadixHeap keys must not be smaller than the last popped key"
+        );
+        let bucket = self.bucket_index(key);
+        self.buckets[bucket].push((key, value));
+        self.size += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<(K, V)> {
+        if self.buckets[0].is_empty() {
+            self.redistribute()?;
+        }
+        self.size -= 1;
+        self.buckets[0].pop()
+    }
+
+    /// Returns the smallest key in the heap.
+    pub fn peek_key(&mut self) -> Option<K> {
+        if self.buckets[0].is_empty() {
+            self.redistribute()?;
+        }
+        self.last
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    fn bucket_index(&self, key: K) -> usize {
+        match self.last {
+            Some(last) => key.radix_distance(last) as usize,
+            None => K::BITS as usize,
+        }
+    }
+
+    /// Empty the first non-empty bucket into lower ones around its smallest
+    /// key, which ends up in bucket 0.
+    fn redistribute(&mut self) -> Option<()> {
+        let index = self.buckets.iter().position(|bucket| !bucket.is_empty())?;
+        let bucket = core::mem::take(&mut self.buckets[index]);
+        self.last = bucket.iter().map(|(key, _)| *key).min();
+        for (key, value) in bucket {
+            let bucket_index = self.bucket_index(key);
+            self.buckets[bucket_index].push((key, value));
+        }
+        Some(())
+    }
+}
+
+impl<K: RadixKey, V> Default for RadixHeap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_heap() -> RadixHeap<u64, char> {
+        let mut heap = RadixHeap::new();
+        for (key, value) in [(9, 'a'), (10, 'b'), (3, 'c'), (8, 'd'), (7, 'e'), (5, 'f')] {
+            heap.push(key, value);
+        }
+        heap
+    }
+
+    #[test]
+    fn it_should_pop_in_ascending_key_order() {
+        let expected_ordering = vec![(3, 'c'), (5, 'f'), (7, 'e'), (8, 'd'), (9, 'a'), (10, 'b')];
+        let mut heap = create_heap();
+        assert_eq!(Some(3), heap.peek_key());
+        for item in expected_ordering {
+            assert_eq!(Some(item), heap.pop());
+        }
+        assert_eq!(None, heap.pop());
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn it_should_accept_monotone_pushes_between_pops() {
+        let mut heap = create_heap();
+        assert_eq!(Some((3, 'c')), heap.pop());
+        heap.push(3, 'g');
+        heap.push(u64::MAX, 'h');
+        heap.push(6, 'i');
+        let keys: Vec<u64> = std::iter::from_fn(|| heap.pop().map(|(key, _)| key)).collect();
+        assert_eq!(vec![3, 5, 6, 7, 8, 9, 10, u64::MAX], keys);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "must not be smaller than the last popped key")]
+    fn it_should_reject_non_monotone_keys_in_debug_builds() {
+        let mut heap = create_heap();
+        heap.pop();
+        heap.push(2, 'z');
+    }
+}
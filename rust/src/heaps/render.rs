@@ -0,0 +1,127 @@
+//! Text renderings of an array-backed heap as the tree it encodes, where the
+//! children of `index` start at `get_first_child_index(index, branch_factor)`.
+
+use alloc::string::String;
+use core::fmt::{Debug, Write};
+
+use super::d_heap::get_first_child_index;
+
+/// Debug renderings of a heap as the tree it encodes.
+pub trait Render {
+    /// Renders the heap as a Graphviz digraph, one node per element with an
+    /// edge from each parent to its children.
+    fn to_dot(&self) -> String;
+
+    /// Renders the heap as an indented tree, e.g. to print from a test.
+    fn ascii_tree(&self) -> String;
+}
+
+fn children(index: usize, len: usize, branch_factor: usize) -> core::ops::Range<usize> {
+    let first_child_index = get_first_child_index(index, branch_factor);
+    first_child_index.min(len)..(first_child_index + branch_factor).min(len)
+}
+
+/// Escapes `label` for use inside a double quoted DOT string.
+pub(crate) fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Renders `elements` as a Graphviz digraph with one node per slot, named
+/// after its index.
+pub(crate) fn to_dot<T: Debug>(name: &str, elements: &[T], branch_factor: usize) -> String {
+    let mut dot = String::new();
+    writeln!(dot, "digraph {name} {{").unwrap();
+    writeln!(dot, "    node [shape=circle];").unwrap();
+    for (index, element) in elements.iter().enumerate() {
+        let label = escape(&alloc::format!("{element:?}"));
+        writeln!(dot, "    n{index} [label=\"{label}\"];").unwrap();
+    }
+    for index in 0..elements.len() {
+        for child_index in children(index, elements.len(), branch_factor) {
+            writeln!(dot, "    n{index} -> n{child_index};").unwrap();
+        }
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// Renders `elements` as an indented tree, one element per line, root first.
+pub(crate) fn ascii_tree<T: Debug>(elements: &[T], branch_factor: usize) -> String {
+    let mut tree = String::new();
+    if let Some(root) = elements.first() {
+        writeln!(tree, "{root:?}").unwrap();
+        write_children(&mut tree, elements, branch_factor, 0, "");
+    }
+    tree
+}
+
+fn write_children<T: Debug>(
+    tree: &mut String,
+    elements: &[T],
+    branch_factor: usize,
+    index: usize,
+    prefix: &str,
+) {
+    let children = children(index, elements.len(), branch_factor);
+    let last_child_index = children.end.saturating_sub(1);
+    for child_index in children {
+        let is_last = child_index == last_child_index;
+        let (branch, indent) = if is_last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        writeln!(tree, "{prefix}{branch}{:?}", elements[child_index]).unwrap();
+        let prefix = alloc::format!("{prefix}{indent}");
+        write_children(tree, elements, branch_factor, child_index, &prefix);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_values() -> Vec<u64> {
+        vec![10, 9, 9, 8, 7, 5, 3, 8]
+    }
+
+    #[test]
+    fn it_should_draw_each_level_of_the_tree() {
+        let expected = "\
+10
+├── 9
+│   ├── 7
+│   ├── 5
+│   └── 3
+├── 9
+│   └── 8
+└── 8
+";
+        assert_eq!(expected, ascii_tree(&create_values(), 3));
+        assert_eq!("", ascii_tree::<u64>(&[], 3));
+    }
+
+    #[test]
+    fn it_should_export_edges_from_parents_to_children() {
+        let dot = to_dot("DHeap", &create_values()[..4], 2);
+        let expected = "\
+digraph DHeap {
+    node [shape=circle];
+    n0 [label=\"10\"];
+    n1 [label=\"9\"];
+    n2 [label=\"9\"];
+    n3 [label=\"8\"];
+    n0 -> n1;
+    n0 -> n2;
+    n1 -> n3;
+}
+";
+        assert_eq!(expected, dot);
+    }
+
+    #[test]
+    fn it_should_escape_quotes_in_labels() {
+        let dot = to_dot("DHeap", &["say \"hi\""], 2);
+        assert!(dot.contains(r#"n0 [label="\"say \\\"hi\\\"\""];"#));
+    }
+}
@@ -0,0 +1,79 @@
+use super::d_heap::{bubble_up_by, get_parent_index, heapify_by, push_down_by};
+
+/// Arrange `data` into a `B`-ary max-heap in O(n).
+pub fn heapify<T: Ord, const B: usize>(data: &mut [T]) {
+    heapify_by::<T, _, B>(data, &mut T::lt);
+}
+
+/// Move the element at `index` down until neither of its children rank above
+/// it, returning the index it settled at.
+///
+/// # Panics
+///
+/// Panics if `index` is out of bounds.
+pub fn sift_down<T: Ord, const B: usize>(data: &mut [T], index: usize) -> usize {
+    assert!(index < data.len(), "index out of bounds");
+    push_down_by::<T, _, B>(data, index, &mut T::lt).end
+}
+
+/// Move the element at `index` up until its parent ranks at least as high,
+/// returning the index it settled at.
+///
+/// # Panics
+///
+/// Panics if `index` is out of bounds.
+pub fn sift_up<T: Ord, const B: usize>(data: &mut [T], index: usize) -> usize {
+    assert!(index < data.len(), "index out of bounds");
+    bubble_up_by::<T, _, B>(data, index, &mut T::lt).end
+}
+
+/// Returns true if `data` satisfies the `B`-ary max-heap property.
+pub fn is_heap<T: Ord, const B: usize>(data: &[T]) -> bool {
+    (1..data.len()).all(|index| data[get_parent_index(index, B)] >= data[index])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_values() -> Vec<u64> {
+        vec![9, 10, 9, 8, 7, 5, 3, 8]
+    }
+
+    #[test]
+    fn it_should_heapify_a_slice() {
+        let mut values = create_values();
+        assert!(!is_heap::<_, 3>(&values));
+        heapify::<_, 3>(&mut values);
+        assert!(is_heap::<_, 3>(&values));
+        assert_eq!(10, values[0]);
+    }
+
+    #[test]
+    fn it_should_sift_up_a_pushed_element() {
+        let mut values = create_values();
+        heapify::<_, 2>(&mut values);
+        values.push(13);
+        let last = values.len() - 1;
+        assert_eq!(0, sift_up::<_, 2>(&mut values, last));
+        assert!(is_heap::<_, 2>(&values));
+    }
+
+    #[test]
+    fn it_should_sift_down_a_replaced_root() {
+        let mut values = create_values();
+        heapify::<_, 4>(&mut values);
+        values[0] = 1;
+        let position = sift_down::<_, 4>(&mut values, 0);
+        assert_eq!(1, values[position]);
+        assert!(is_heap::<_, 4>(&values));
+        assert_eq!(9, values[0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn it_should_panic_when_sifting_outside_the_slice() {
+        let mut values = create_values();
+        sift_up::<_, 2>(&mut values, 8);
+    }
+}
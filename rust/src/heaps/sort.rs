@@ -0,0 +1,122 @@
+use core::cmp::Ordering;
+
+use super::d_heap::{heapify_by, push_down_by};
+
+/// Sort `data` in ascending order in place using a `B`-ary heap.
+///
+/// Builds a max-heap over the slice and then repeatedly swaps the root to the
+/// end of the shrinking unsorted prefix, so no extra allocation is needed.
+pub fn heapsort<T: Ord, const B: usize>(data: &mut [T]) {
+    heapsort_impl::<T, _, B>(data, &mut T::lt);
+}
+
+/// Sort `data` in place according to `compare` using a `B`-ary heap.
+pub fn heapsort_by<T, F, const B: usize>(data: &mut [T], mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    heapsort_impl::<T, _, B>(data, &mut |a: &T, b: &T| compare(a, b) == Ordering::Less);
+}
+
+/// Rearrange `data` so that its first `k` elements are the `k` smallest, in
+/// ascending order. The order of the remaining elements is unspecified.
+///
+/// Runs in O(n log k) by keeping a max-heap of the best `k` seen so far at the
+/// front of the slice.
+pub fn partial_sort<T: Ord, const B: usize>(data: &mut [T], k: usize) {
+    partial_sort_impl::<T, _, B>(data, k, &mut T::lt);
+}
+
+/// [`partial_sort`] according to `compare`.
+pub fn partial_sort_by<T, F, const B: usize>(data: &mut [T], k: usize, mut compare: F)
+where
+    F: FnMut(&T, &T) -> Ordering,
+{
+    partial_sort_impl::<T, _, B>(data, k, &mut |a: &T, b: &T| compare(a, b) == Ordering::Less);
+}
+
+fn heapsort_impl<T, F, const B: usize>(data: &mut [T], is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    heapify_by::<T, F, B>(data, is_less);
+    for end in (1..data.len()).rev() {
+        data.swap(0, end);
+        push_down_by::<T, F, B>(&mut data[..end], 0, is_less);
+    }
+}
+
+fn partial_sort_impl<T, F, const B: usize>(data: &mut [T], k: usize, is_less: &mut F)
+where
+    F: FnMut(&T, &T) -> bool,
+{
+    let k = core::cmp::min(k, data.len());
+    if k == 0 {
+        return;
+    }
+    let (kept, rest) = data.split_at_mut(k);
+    heapify_by::<T, F, B>(kept, is_less);
+    for candidate in rest.iter_mut() {
+        if is_less(candidate, &kept[0]) {
+            core::mem::swap(candidate, &mut kept[0]);
+            push_down_by::<T, F, B>(kept, 0, is_less);
+        }
+    }
+    heapsort_impl::<T, F, B>(kept, is_less);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_values() -> Vec<u64> {
+        vec![9, 10, 9, 8, 7, 5, 3, 8, 1, 14, 2]
+    }
+
+    #[test]
+    fn it_should_sort_in_ascending_order() {
+        let mut values = create_values();
+        let mut expected = values.clone();
+        expected.sort();
+        heapsort::<_, 3>(&mut values);
+        assert_eq!(expected, values);
+    }
+
+    #[test]
+    fn it_should_sort_with_a_custom_comparator() {
+        let mut values = create_values();
+        let mut expected = values.clone();
+        expected.sort_by(|a, b| b.cmp(a));
+        heapsort_by::<_, _, 2>(&mut values, |a, b| b.cmp(a));
+        assert_eq!(expected, values);
+    }
+
+    #[test]
+    fn it_should_handle_empty_and_single_element_slices() {
+        let mut empty: Vec<u64> = vec![];
+        heapsort::<_, 4>(&mut empty);
+        assert!(empty.is_empty());
+        let mut single = vec![1];
+        heapsort::<_, 4>(&mut single);
+        assert_eq!(vec![1], single);
+    }
+
+    #[test]
+    fn it_should_partially_sort_the_smallest_elements() {
+        let mut values = create_values();
+        partial_sort::<_, 3>(&mut values, 4);
+        assert_eq!(vec![1, 2, 3, 5], values[..4]);
+        let mut rest = values[4..].to_vec();
+        rest.sort();
+        assert_eq!(vec![7, 8, 8, 9, 9, 10, 14], rest);
+    }
+
+    #[test]
+    fn it_should_partially_sort_everything_when_k_exceeds_length() {
+        let mut values = create_values();
+        let mut expected = values.clone();
+        expected.sort_by(|a, b| b.cmp(a));
+        partial_sort_by::<_, _, 2>(&mut values, 100, |a, b| b.cmp(a));
+        assert_eq!(expected, values);
+    }
+}
@@ -0,0 +1,122 @@
+use core::fmt;
+
+use super::d_heap::Sift;
+
+/// Work done by a heap's sifts, recorded when the crate is built with the
+/// `instrumented` feature.
+///
+/// A move is an element written into a slot it did not hold before: each one a
+/// sift shifts out of the way, and the sifted element itself once it lands
+/// somewhere new.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HeapStats {
+    pub comparisons: u64,
+    pub moves: u64,
+    pub sifts: u64,
+    /// Levels travelled across all sifts.
+    pub sift_levels: u64,
+    pub max_sift_depth: u64,
+}
+
+impl HeapStats {
+    pub fn mean_sift_depth(&self) -> f64 {
+        if self.sifts == 0 {
+            0.0
+        } else {
+            self.sift_levels as f64 / self.sifts as f64
+        }
+    }
+}
+
+impl fmt::Display for HeapStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "comparisons: {}, moves: {}, sifts: {}, mean sift depth: {:.2}, max sift depth: {}",
+            self.comparisons,
+            self.moves,
+            self.sifts,
+            self.mean_sift_depth(),
+            self.max_sift_depth
+        )
+    }
+}
+
+/// Collects [`HeapStats`] for a heap. Without the `instrumented` feature it is
+/// zero sized and recording compiles away.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct StatsRecorder {
+    #[cfg(feature = "instrumented")]
+    stats: HeapStats,
+}
+
+impl StatsRecorder {
+    pub(crate) const fn new() -> Self {
+        StatsRecorder {
+            #[cfg(feature = "instrumented")]
+            stats: HeapStats {
+                comparisons: 0,
+                moves: 0,
+                sifts: 0,
+                sift_levels: 0,
+                max_sift_depth: 0,
+            },
+        }
+    }
+
+    #[inline]
+    pub(crate) fn record_comparison(&mut self) {
+        #[cfg(feature = "instrumented")]
+        {
+            self.stats.comparisons += 1;
+        }
+    }
+
+    /// `<` on `T`, recording each comparison.
+    #[inline]
+    pub(crate) fn less_than<T: Ord>(&mut self) -> impl FnMut(&T, &T) -> bool + '_ {
+        |a, b| {
+            self.record_comparison();
+            a < b
+        }
+    }
+
+    /// Records `sift` of the element that started at `start` in a heap with
+    /// the given branch factor.
+    #[inline]
+    pub(crate) fn record_sift(&mut self, start: usize, sift: Sift, branch_factor: usize) {
+        #[cfg(feature = "instrumented")]
+        {
+            let (ancestor, descendant) = (start.min(sift.end), start.max(sift.end));
+            let levels = levels_between(ancestor, descendant, branch_factor) as u64;
+            self.stats.sifts += 1;
+            self.stats.sift_levels += levels;
+            self.stats.max_sift_depth = self.stats.max_sift_depth.max(levels);
+            self.stats.moves += sift.moves as u64;
+        }
+        #[cfg(not(feature = "instrumented"))]
+        let _ = (start, sift, branch_factor);
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub(crate) fn stats(&self) -> HeapStats {
+        self.stats
+    }
+
+    #[cfg(feature = "instrumented")]
+    pub(crate) fn reset(&mut self) {
+        self.stats = HeapStats::default();
+    }
+}
+
+/// Number of levels between `ancestor` and `descendant` in a heap with the
+/// given branch factor.
+#[cfg(feature = "instrumented")]
+fn levels_between(ancestor: usize, mut descendant: usize, branch_factor: usize) -> usize {
+    let mut levels = 0;
+    while descendant > ancestor {
+        descendant = (descendant - 1) / branch_factor;
+        levels += 1;
+    }
+    levels
+}
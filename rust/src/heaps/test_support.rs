@@ -0,0 +1,199 @@
+//! Helpers shared by the heaps' test modules.
+
+use core::cmp::Ordering;
+use std::{
+    cell::Cell,
+    panic::{self, AssertUnwindSafe},
+    rc::Rc,
+};
+
+/// What [`priority_queue_tests`] needs from a heap of `u64`s.
+pub(crate) trait TestHeap: Default {
+    fn insert(&mut self, element: u64);
+    fn top(&mut self) -> Option<u64>;
+    fn peek(&self) -> Option<u64>;
+    fn size(&self) -> usize;
+}
+
+/// The heap the d_heap tests start from.
+pub(crate) fn create_heap<H: TestHeap>() -> H {
+    let mut heap = H::default();
+    for number in [9, 10, 9, 8, 7, 5, 3, 8] {
+        heap.insert(number);
+    }
+    heap
+}
+
+pub(crate) fn assert_expected_order<H: TestHeap>() {
+    let expected_ordering = vec![10, 9, 9, 8, 8, 7, 5, 3];
+    let mut heap: H = create_heap();
+    for number in expected_ordering {
+        let actual = heap.top().unwrap();
+        assert_eq!(actual, number);
+    }
+    assert_eq!(0, heap.size());
+}
+
+pub(crate) fn assert_inserts_elements<H: TestHeap>() {
+    let expected_ordering = vec![13, 11, 10, 9, 9, 8, 8, 7, 5, 3];
+    let mut heap: H = create_heap();
+    heap.insert(13);
+    heap.insert(11);
+    assert_eq!(Some(13), heap.peek());
+    assert_eq!(10, heap.size());
+    for number in expected_ordering {
+        let actual = heap.top().unwrap();
+        assert_eq!(actual, number);
+    }
+    assert_eq!(None, heap.peek());
+    assert_eq!(0, heap.size());
+}
+
+/// Adds the d_heap priority queue tests for `$heap`, which implements
+/// [`TestHeap`], to the calling test module.
+macro_rules! priority_queue_tests {
+    ($heap:ty) => {
+        #[test]
+        fn it_should_have_expected_order() {
+            $crate::heaps::test_support::assert_expected_order::<$heap>();
+        }
+
+        #[test]
+        fn it_should_be_able_to_insert_elements() {
+            $crate::heaps::test_support::assert_inserts_elements::<$heap>();
+        }
+    };
+}
+
+pub(crate) use priority_queue_tests;
+
+/// Adds the tests every `DHeap` variant shares to the calling test module.
+/// `$with_capacity` builds an empty binary heap of `u64`s with room for the
+/// given number of elements.
+macro_rules! d_heap_variant_tests {
+    ($with_capacity:expr) => {
+        #[test]
+        fn it_should_retain_matching_elements_in_heap_order() {
+            let mut heap = ($with_capacity)(7);
+            for number in [9, 10, 3, 5, 7, 8, 11] {
+                heap.insert(number);
+            }
+            heap.retain(|&number| number > 5);
+            assert!(!heap.contains(&3));
+            assert!(heap.contains(&7));
+            heap.remove(9);
+            let popped: Vec<u64> = (0..heap.size()).map(|_| heap.top().unwrap()).collect();
+            assert_eq!(vec![11, 10, 8, 7], popped);
+        }
+
+        #[test]
+        fn it_should_drain_and_manage_capacity() {
+            let mut heap = ($with_capacity)(16);
+            assert!(heap.capacity() >= 16);
+            for number in [9, 10, 3, 5] {
+                heap.insert(number);
+            }
+            let mut drained: Vec<u64> = heap.drain().collect();
+            drained.sort_unstable();
+            assert_eq!(vec![3, 5, 9, 10], drained);
+            assert!(heap.is_empty());
+            assert!(!heap.contains(&9));
+            assert!(heap.capacity() >= 16);
+
+            heap.shrink_to_fit();
+            assert_eq!(0, heap.capacity());
+            heap.reserve(10);
+            assert!(heap.capacity() >= 10);
+            heap.insert(1);
+            heap.clear();
+            assert!(!heap.contains(&1));
+            assert!(heap.is_empty());
+        }
+
+        /// Inserting 1..=7 into a binary heap bubbles every element to the
+        /// root.
+        #[cfg(feature = "instrumented")]
+        #[test]
+        fn it_should_count_comparisons_and_moves_when_instrumented() {
+            let mut heap = ($with_capacity)(7);
+            for number in 1..=7 {
+                heap.insert(number);
+            }
+            let expected = $crate::heaps::HeapStats {
+                comparisons: 10,
+                moves: 16,
+                sifts: 7,
+                sift_levels: 10,
+                max_sift_depth: 2,
+            };
+            assert_eq!(expected, heap.stats());
+            heap.reset_stats();
+            assert_eq!($crate::heaps::HeapStats::default(), heap.stats());
+            assert_eq!(Some(7), heap.top());
+            assert_eq!(1, heap.stats().sifts);
+        }
+    };
+}
+
+pub(crate) use d_heap_variant_tests;
+
+thread_local! {
+    static COMPARISONS_LEFT: Cell<usize> = const { Cell::new(usize::MAX) };
+}
+
+/// Counts its drops so tests can check nothing is leaked or dropped twice, and
+/// panics on the comparison that uses up the budget [`panics_on_comparison`]
+/// sets.
+pub(crate) struct Tracked {
+    pub(crate) value: u64,
+    drops: Rc<Cell<usize>>,
+}
+
+impl Tracked {
+    pub(crate) fn new(value: u64, drops: &Rc<Cell<usize>>) -> Self {
+        Tracked {
+            value,
+            drops: drops.clone(),
+        }
+    }
+}
+
+impl Ord for Tracked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let left = COMPARISONS_LEFT.get() - 1;
+        COMPARISONS_LEFT.set(left);
+        if left == 0 {
+            panic!("comparison budget used up");
+        }
+        self.value.cmp(&other.value)
+    }
+}
+
+impl PartialOrd for Tracked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Tracked {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl Eq for Tracked {}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.drops.set(self.drops.get() + 1);
+    }
+}
+
+/// Runs `operation` with a comparison budget of `nth`, returning whether
+/// it panicked.
+pub(crate) fn panics_on_comparison<R>(nth: usize, operation: impl FnOnce() -> R) -> bool {
+    COMPARISONS_LEFT.set(nth);
+    let panicked = panic::catch_unwind(AssertUnwindSafe(operation)).is_err();
+    COMPARISONS_LEFT.set(usize::MAX);
+    panicked
+}
@@ -0,0 +1,141 @@
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+
+use super::d_heap::DHeap;
+
+/// Keeps the `k` largest items offered to it.
+///
+/// The kept items are stored in a `B`-ary min-heap (a [`DHeap`] over
+/// [`Reverse`]), so the smallest kept item is always at the root and a new
+/// item only costs a single push down when it displaces it.
+pub struct TopK<T: Ord, const B: usize> {
+    heap: DHeap<Reverse<T>, B>,
+    k: usize,
+}
+
+impl<T: Ord, const B: usize> TopK<T, B> {
+    pub fn new(k: usize) -> Self {
+        TopK {
+            heap: DHeap::new(Vec::with_capacity(k)),
+            k,
+        }
+    }
+
+    /// Offers `item`, returning whichever item no longer makes the cut: the
+    /// previously smallest kept item, `item` itself, or `None` if there was
+    /// still room.
+    pub fn offer(&mut self, item: T) -> Option<T> {
+        if self.heap.size() < self.k {
+            self.heap.insert(Reverse(item));
+            return None;
+        }
+        match self.heap.peek() {
+            Some(Reverse(smallest)) if *smallest < item => {
+                self.heap.replace_top(Reverse(item)).map(|Reverse(e)| e)
+            }
+            _ => Some(item),
+        }
+    }
+
+    /// Folds the items kept by `other` into this one, e.g. to combine partial
+    /// results computed on separate threads.
+    pub fn merge(&mut self, mut other: TopK<T, B>) {
+        while let Some(Reverse(item)) = other.heap.top() {
+            self.offer(item);
+        }
+    }
+
+    /// Returns the smallest item currently kept, which is the bar a new item
+    /// has to beat once the `TopK` is full.
+    pub fn peek(&self) -> Option<&T> {
+        self.heap.peek().map(|Reverse(e)| e)
+    }
+
+    /// Consumes the `TopK` and returns the kept items in ascending order.
+    pub fn into_sorted_vec(mut self) -> Vec<T> {
+        let mut sorted = Vec::with_capacity(self.heap.size());
+        while let Some(Reverse(item)) = self.heap.top() {
+            sorted.push(item);
+        }
+        sorted
+    }
+
+    pub fn k(&self) -> usize {
+        self.k
+    }
+
+    pub fn size(&self) -> usize {
+        self.heap.size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl<T: Ord, const B: usize> Extend<T> for TopK<T, B> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        for item in iter {
+            self.offer(item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_top_k() -> TopK<u64, 3> {
+        let mut top_k = TopK::new(4);
+        top_k.extend(vec![9, 10, 9, 8, 7, 5, 3, 8]);
+        top_k
+    }
+
+    #[test]
+    fn it_should_keep_the_largest_items() {
+        let top_k = create_top_k();
+        assert_eq!(4, top_k.size());
+        assert_eq!(Some(&8), top_k.peek());
+        assert_eq!(vec![8, 9, 9, 10], top_k.into_sorted_vec());
+    }
+
+    #[test]
+    fn it_should_return_evicted_items_from_offer() {
+        let mut top_k = create_top_k();
+        assert_eq!(Some(8), top_k.offer(11));
+        assert_eq!(Some(2), top_k.offer(2));
+        assert_eq!(vec![9, 9, 10, 11], top_k.into_sorted_vec());
+
+        let mut none_kept: TopK<u64, 3> = TopK::new(0);
+        assert_eq!(Some(1), none_kept.offer(1));
+        assert!(none_kept.is_empty());
+    }
+
+    #[test]
+    fn it_should_merge_partial_results_from_threads() {
+        let values: Vec<u64> = (0..10_000).map(|n| (n * 7_919) % 10_007).collect();
+        let partials: Vec<TopK<u64, 3>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = values
+                .chunks(1_000)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut top_k = TopK::new(10);
+                        top_k.extend(chunk.iter().copied());
+                        top_k
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        let mut merged = TopK::new(10);
+        for partial in partials {
+            merged.merge(partial);
+        }
+        let mut expected = values.clone();
+        expected.sort();
+        assert_eq!(
+            expected[expected.len() - 10..],
+            merged.into_sorted_vec()[..]
+        );
+    }
+}
@@ -1 +1,9 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
+
+pub mod advanced;
+pub mod compression;
 pub mod heaps;
+#[cfg(feature = "std")]
+pub mod timers;
@@ -1,5 +1,65 @@
-pub mod heaps;
+use std::{env, fs, process::ExitCode};
 
-fn main() {
-    println!("Hey");
+use advanced_datastructures::compression::format;
+use advanced_datastructures::heaps::d_heap::DHeap;
+use advanced_datastructures::heaps::Render;
+
+const USAGE: &str = "\
+usage: advanced_datastructures compress <in> <out>
+       advanced_datastructures decompress <in> <out>
+       advanced_datastructures tree <number>...";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    match args {
+        [command, input, output] if command == "compress" => {
+            let data = read(input)?;
+            let compressed = format::compress(&data);
+            write(output, &compressed)?;
+            println!(
+                "compressed {input} from {} to {} bytes",
+                data.len(),
+                compressed.len()
+            );
+            Ok(())
+        }
+        [command, input, output] if command == "decompress" => {
+            let compressed = read(input)?;
+            let data = format::decompress(&compressed)
+                .map_err(|error| format!("error: cannot decompress {input}: {error}"))?;
+            write(output, &data)
+        }
+        [command, numbers @ ..] if command == "tree" => {
+            let numbers = numbers
+                .iter()
+                .map(|number| {
+                    number
+                        .parse()
+                        .map_err(|_| format!("error: {number} is not a whole number"))
+                })
+                .collect::<Result<Vec<u64>, _>>()?;
+            let heap: DHeap<u64, 3> = DHeap::new(numbers);
+            print!("{}", heap.ascii_tree());
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|error| format!("error: cannot read {path}: {error}"))
+}
+
+fn write(path: &str, data: &[u8]) -> Result<(), String> {
+    fs::write(path, data).map_err(|error| format!("error: cannot write {path}: {error}"))
 }
@@ -0,0 +1,3 @@
+mod timing_wheel;
+
+pub use timing_wheel::{TimerKey, TimingWheel};
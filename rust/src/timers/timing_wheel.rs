@@ -0,0 +1,319 @@
+use std::{
+    cmp::Reverse,
+    time::{Duration, Instant},
+};
+
+use crate::heaps::d_heap::DHeap;
+
+/// Identifies a timer in a [`TimingWheel`] for `cancel`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TimerKey {
+    index: usize,
+    stamp: u64,
+}
+
+struct Slot<T> {
+    stamp: u64,
+    entry: Option<(u64, T)>,
+}
+
+// (slab index, stamp) of a timer parked in a wheel slot
+type SlotEntry = (usize, u64);
+
+/// A hierarchical timing wheel for large numbers of timers.
+///
+/// Time advances in ticks of a fixed granularity. Level `l` has
+/// `slots_per_level` slots each spanning `slots_per_level^l` ticks, so
+/// inserting and cancelling are O(1) and a timer is moved down a level at most
+/// once per level as its deadline approaches ("cascading"). Timers further out
+/// than the wheel covers wait in a min-ordered [`DHeap`] until they come into
+/// range.
+///
+/// Deadlines are rounded up to the next tick, so timers never fire early.
+pub struct TimingWheel<T> {
+    start: Instant,
+    granularity: Duration,
+    slot_bits: u32,
+    levels: Vec<Vec<Vec<SlotEntry>>>,
+    overflow: DHeap<Reverse<(u64, u64, usize)>, 4>,
+    // timers whose deadline had already passed when they were inserted
+    due: Vec<SlotEntry>,
+    slots: Vec<Slot<T>>,
+    free_slots: Vec<usize>,
+    elapsed: u64,
+    next_stamp: u64,
+    size: usize,
+}
+
+impl<T> TimingWheel<T> {
+    /// Creates a wheel starting at `start` with `levels` levels of
+    /// `slots_per_level` slots, each lowest level slot lasting `granularity`.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `slots_per_level` is a power of two greater than one,
+    /// `levels` is at least one and the wheel spans at most 2^63 ticks.
+    pub fn new(
+        start: Instant,
+        granularity: Duration,
+        slots_per_level: usize,
+        levels: usize,
+    ) -> Self {
+        assert!(
+            slots_per_level > 1 && slots_per_level.is_power_of_two(),
+            "slots_per_level must be a power of two greater than one"
+        );
+        assert!(levels > 0, "a timing wheel needs at least one level");
+        assert!(!granularity.is_zero(), "granularity must not be zero");
+        let slot_bits = slots_per_level.trailing_zeros();
+        assert!(
+            slot_bits as usize * levels < 64,
+            "timing wheel spans too many ticks"
+        );
+        TimingWheel {
+            start,
+            granularity,
+            slot_bits,
+            levels: (0..levels)
+                .map(|_| (0..slots_per_level).map(|_| Vec::new()).collect())
+                .collect(),
+            overflow: DHeap::new(vec![]),
+            due: Vec::new(),
+            slots: Vec::new(),
+            free_slots: Vec::new(),
+            elapsed: 0,
+            next_stamp: 0,
+            size: 0,
+        }
+    }
+
+    pub fn insert_at(&mut self, item: T, deadline: Instant) -> TimerKey {
+        let since_start = deadline.saturating_duration_since(self.start);
+        let ticks = since_start.as_nanos().div_ceil(self.granularity.as_nanos());
+        self.insert_at_tick(item, u64::try_from(ticks).unwrap_or(u64::MAX))
+    }
+
+    pub fn insert_after(&mut self, item: T, delay: Duration) -> TimerKey {
+        let ticks = delay.as_nanos().div_ceil(self.granularity.as_nanos());
+        let deadline = self
+            .elapsed
+            .saturating_add(u64::try_from(ticks).unwrap_or(u64::MAX));
+        self.insert_at_tick(item, deadline)
+    }
+
+    /// Removes the timer behind `key`, returning its item if it hadn't fired.
+    pub fn cancel(&mut self, key: TimerKey) -> Option<T> {
+        let slot = self
+            .slots
+            .get_mut(key.index)
+            .filter(|slot| slot.stamp == key.stamp)?;
+        let (_, item) = slot.entry.take()?;
+        self.free_slots.push(key.index);
+        self.size -= 1;
+        Some(item)
+    }
+
+    /// Moves time forward to `now`, returning the items of every timer that
+    /// fired on the way in deadline order (ties in no particular order).
+    pub fn advance(&mut self, now: Instant) -> Vec<T> {
+        let since_start = now.saturating_duration_since(self.start);
+        let target = since_start.as_nanos() / self.granularity.as_nanos();
+        let target = u64::try_from(target).unwrap_or(u64::MAX);
+        let mut fired = Vec::new();
+        for entry in std::mem::take(&mut self.due) {
+            fired.extend(self.take_live(entry));
+        }
+        while self.elapsed < target && self.size > fired.len() {
+            self.elapsed += 1;
+            self.cascade();
+            let slot = self.slot_index(self.elapsed, 0);
+            let mut entries = std::mem::take(&mut self.due);
+            entries.append(&mut self.levels[0][slot]);
+            for entry in entries {
+                fired.extend(self.take_live(entry));
+            }
+        }
+        // nothing live is left, so skip the remaining ticks and drop the
+        // cancelled entries still parked in the wheel
+        if self.elapsed < target && self.size == fired.len() {
+            self.elapsed = target;
+            self.levels.iter_mut().flatten().for_each(Vec::clear);
+            self.overflow = DHeap::new(vec![]);
+        }
+        self.size -= fired.len();
+        fired
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.size == 0
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The time the wheel has advanced to.
+    pub fn now(&self) -> Instant {
+        let nanos = self.granularity.as_nanos() * u128::from(self.elapsed);
+        self.start
+            + Duration::new(
+                (nanos / 1_000_000_000) as u64,
+                (nanos % 1_000_000_000) as u32,
+            )
+    }
+
+    fn insert_at_tick(&mut self, item: T, deadline: u64) -> TimerKey {
+        self.next_stamp += 1;
+        let stamp = self.next_stamp;
+        let slot = Slot {
+            stamp,
+            entry: Some((deadline, item)),
+        };
+        let index = match self.free_slots.pop() {
+            Some(index) => {
+                self.slots[index] = slot;
+                index
+            }
+            None => {
+                self.slots.push(slot);
+                self.slots.len() - 1
+            }
+        };
+        self.place(deadline, (index, stamp));
+        self.size += 1;
+        TimerKey { index, stamp }
+    }
+
+    /// Put an entry in the level whose slot span matches the highest bit its
+    /// deadline differs from the current tick in.
+    fn place(&mut self, deadline: u64, entry: SlotEntry) {
+        if deadline <= self.elapsed {
+            self.due.push(entry);
+            return;
+        }
+        let highest_bit = 63 - (deadline ^ self.elapsed).leading_zeros();
+        let level = (highest_bit / self.slot_bits) as usize;
+        if level >= self.levels.len() {
+            self.overflow.insert(Reverse((deadline, entry.1, entry.0)));
+        } else {
+            let slot = self.slot_index(deadline, level);
+            self.levels[level][slot].push(entry);
+        }
+    }
+
+    /// On entering a new slot of a higher level, redistribute its timers into
+    /// the levels below, highest level first.
+    fn cascade(&mut self) {
+        let wheel_bits = self.slot_bits * self.levels.len() as u32;
+        if self.elapsed & ((1 << wheel_bits) - 1) == 0 {
+            while let Some(&Reverse((deadline, stamp, index))) = self.overflow.peek() {
+                if deadline >> wheel_bits != self.elapsed >> wheel_bits {
+                    break;
+                }
+                self.overflow.top();
+                self.place(deadline, (index, stamp));
+            }
+        }
+        for level in (1..self.levels.len()).rev() {
+            let level_bits = self.slot_bits * level as u32;
+            if self.elapsed & ((1 << level_bits) - 1) != 0 {
+                continue;
+            }
+            let slot = self.slot_index(self.elapsed, level);
+            for entry in std::mem::take(&mut self.levels[level][slot]) {
+                if let Some(deadline) = self.live_deadline(entry) {
+                    self.place(deadline, entry);
+                }
+            }
+        }
+    }
+
+    fn slot_index(&self, tick: u64, level: usize) -> usize {
+        let mask = (1 << self.slot_bits) - 1;
+        ((tick >> (self.slot_bits * level as u32)) & mask) as usize
+    }
+
+    fn live_deadline(&self, (index, stamp): SlotEntry) -> Option<u64> {
+        let slot = &self.slots[index];
+        match &slot.entry {
+            Some((deadline, _)) if slot.stamp == stamp => Some(*deadline),
+            _ => None,
+        }
+    }
+
+    fn take_live(&mut self, (index, stamp): SlotEntry) -> Option<T> {
+        let slot = &mut self.slots[index];
+        if slot.stamp != stamp {
+            return None;
+        }
+        let (_, item) = slot.entry.take()?;
+        self.free_slots.push(index);
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_wheel() -> (TimingWheel<u64>, Instant) {
+        let start = Instant::now();
+        let wheel = TimingWheel::new(start, Duration::from_millis(1), 4, 2);
+        (wheel, start)
+    }
+
+    fn at(start: Instant, millis: u64) -> Instant {
+        start + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn it_should_fire_timers_across_levels_in_order() {
+        let (mut wheel, start) = create_wheel();
+        for millis in [3, 1, 14, 6, 40, 17, 200] {
+            wheel.insert_at(millis, at(start, millis));
+        }
+        assert_eq!(Vec::<u64>::new(), wheel.advance(start));
+        assert_eq!(vec![1, 3], wheel.advance(at(start, 5)));
+        assert_eq!(vec![6, 14], wheel.advance(at(start, 16)));
+        assert_eq!(vec![17, 40], wheel.advance(at(start, 199)));
+        assert_eq!(1, wheel.size());
+        assert_eq!(vec![200], wheel.advance(at(start, 1_000)));
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn it_should_cancel_timers_and_fire_overdue_ones_straight_away() {
+        let (mut wheel, start) = create_wheel();
+        wheel.advance(at(start, 10));
+        let key = wheel.insert_after(7, Duration::from_millis(7));
+        wheel.insert_at(1, at(start, 1));
+        wheel.insert_after(100, Duration::from_millis(100));
+        assert_eq!(Some(7), wheel.cancel(key));
+        assert_eq!(None, wheel.cancel(key));
+        assert_eq!(vec![1], wheel.advance(at(start, 11)));
+        assert_eq!(vec![100], wheel.advance(at(start, 500)));
+        assert_eq!(at(start, 500), wheel.now());
+    }
+
+    #[test]
+    fn it_should_never_fire_early_or_lose_timers() {
+        let (mut wheel, start) = create_wheel();
+        let deadlines: Vec<u64> = (0..2_000).map(|n| (n * 7_919) % 3_001).collect();
+        for &millis in &deadlines {
+            wheel.insert_at(millis, at(start, millis));
+        }
+        let mut fired = Vec::new();
+        for now in (0..=3_000).step_by(37) {
+            for millis in wheel.advance(at(start, now)) {
+                assert!(millis <= now);
+                assert!(millis + 37 > now || now == 0);
+                fired.push(millis);
+            }
+        }
+        fired.extend(wheel.advance(at(start, 3_001)));
+        let mut expected = deadlines;
+        expected.sort();
+        fired.sort();
+        assert_eq!(expected, fired);
+    }
+}